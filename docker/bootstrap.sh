@@ -17,27 +17,44 @@ done
 
 workspace=/workspace
 target_dir="${CARGO_TARGET_DIR}"
-host_target="$(rustc -vV | awk '/host:/ {print $2; exit}')"
 
-# On windows there is no UID and GID and we apply the default
-uid="${UID:-1000}"
-gid="${GID:-1000}"
+# The UID variable is set by bash itself, so it can't be used to pass the user id. On windows
+# there is no UID and GID and we apply the default.
+uid="${CARGO_GUNGRAUN_UID:-1000}"
+gid="${CARGO_GUNGRAUN_GID:-1000}"
 
 if [[ "$USER" != "root" ]]; then
-  echo "${USER}::${uid}:${gid}::${HOME}:${SHELL}" >>/etc/passwd
-  echo "${USER}::${gid}:${USER}" >>/etc/group
+  # With podman's `--userns=keep-id` the user already exists
+  if entry="$(getent passwd "$USER")"; then
+    if [[ "$(cut -d: -f3 <<<"$entry")" != "$uid" ]]; then
+      echo "The user $USER already exists in the image with a different uid than $uid" >&2
+      exit 1
+    fi
+  else
+    getent group "$gid" >/dev/null || echo "${USER}::${gid}:${USER}" >>/etc/group
+    echo "${USER}::${uid}:${gid}::${HOME}:${SHELL}" >>/etc/passwd
+  fi
+
+  # Allow the user to use the kvm device for hardware acceleration
+  if [[ -e /dev/kvm ]]; then
+    kvm_gid="$(stat -c %g /dev/kvm)"
+    getent group "$kvm_gid" >/dev/null || groupadd --gid "$kvm_gid" kvm
+    usermod --append --groups "$kvm_gid" "$USER"
+  fi
 fi
 
+# The qemu runner creates its lock file in /run/lock
+mkdir -p /run/lock
+chmod 1777 /run/lock
+
 mkdir -p "$HOME" "$workspace" "$target_dir" "$GUNGRAUN_HOME"
 chown "${uid}:${gid}" "$HOME" "$workspace" "$target_dir" "$GUNGRAUN_HOME"
 
-if [[ ! -e "$GUNGRAUN_RUNNER" ]]; then
-  archive=gungraun-runner-v${GUNGRAUN_VERSION}-${host_target}
-  cd /tmp
-  wget "https://github.com/gungraun/gungraun/releases/download/v${GUNGRAUN_VERSION}/${archive}.tar.gz"
-  tar xzf "${archive}.tar.gz" --strip-components=1 "${archive}/gungraun-runner"
-  mv gungraun-runner "$GUNGRAUN_RUNNER"
-  rm -rf "${archive}.tar.gz"
+# The runner is downloaded and verified by cargo-gungraun on the host and mounted read-only.
+# A runner from a git or path source of gungraun is built by cargo-gungraun after the bootstrap
+if [[ ! -x "$GUNGRAUN_RUNNER" ]] && [[ -z "${CARGO_GUNGRAUN_RUNNER_SOURCE:-}" ]]; then
+  echo "The gungraun-runner $GUNGRAUN_VERSION was not found at '$GUNGRAUN_RUNNER'" >&2
+  exit 1
 fi
 
 echo "cargo-gungraun: bootstrap finished"
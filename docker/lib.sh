@@ -16,6 +16,19 @@ bail() {
   exit 1
 }
 
+# Print the argument quoted for a POSIX shell
+#
+# The argument is enclosed in single quotes and every single quote is replaced
+# with '\''
+#
+# Parameters:
+#   $1: The string to quote
+shell_quote() {
+  local escaped
+  escaped="${1//\'/\'\\\'\'}"
+  printf "'%s'" "$escaped"
+}
+
 # Return the rust target triple of this host
 host_target() {
   rustc -vV | awk '/host:/ {print $2; exit}'
@@ -21,6 +21,7 @@ lockfile=/run/lock/qemu.lock
 logfile=/tmp/qemu.log
 timeout=120
 no_run=false
+env_file="${CARGO_GUNGRAUN_GUEST_ENV_FILE:-}"
 while [[ "$1" != "--" ]]; do
   case "$1" in
   --qemu-arch)
@@ -43,8 +44,8 @@ while [[ "$1" != "--" ]]; do
     no_run=true
     shift
     ;;
-  --envs)
-    envs="${2:?Missing argument for --envs}"
+  --env-file)
+    env_file="${2:?Missing argument for --env-file}"
     shift 2
     ;;
   --extra-args)
@@ -116,13 +117,25 @@ if_trace set -x
     '-monitor' 'none')
 
   append="console=ttyS0 --"
-  for mount in "workspace:/workspace" "target:/target" "gungraun_home:/gungraun_home" "cargo:/root/.cargo" "rustup:/root/.rustup"; do
+  for mount in "workspace:/workspace" "target:/target" "gungraun_home:/gungraun_home" "cargo:${CARGO_HOME:-/root/.cargo}" "rustup:${RUSTUP_HOME:-/root/.rustup}"; do
     IFS=: read -r tag path <<<"$mount"
     if [[ -e "$path" ]]; then
       qemu_cmd+=("-virtfs" "local,path=${path},security_model=passthrough,mount_tag=${tag}")
       append+=" --mount $mount"
     fi
   done
+
+  # Additional volumes in the form TAG:PATH[:ro] from cargo-gungraun
+  read -r -a extra_mounts <<<"${CARGO_GUNGRAUN_QEMU_MOUNTS:-}"
+  for mount in "${extra_mounts[@]}"; do
+    IFS=: read -r tag path mode <<<"$mount"
+    virtfs="local,path=${path},security_model=passthrough,mount_tag=${tag}"
+    if [[ "$mode" == "ro" ]]; then
+      virtfs+=",readonly=on"
+    fi
+    qemu_cmd+=("-virtfs" "$virtfs")
+    append+=" --mount ${tag}:${path}"
+  done
   qemu_cmd+=("-append" "$append")
 
   if [[ -n "$accel" ]]; then
@@ -223,11 +236,29 @@ if_trace set -x
 ) 9>"$lockfile"
 
 if ! $no_run; then
+  # The remote command is interpreted by the shell of the guest, so everything is
+  # quoted
+  remote_cmd="cd $(shell_quote "$PWD")"
+  if [[ -n "$env_file" ]]; then
+    remote_cmd+=" && . $(shell_quote "$env_file")"
+  fi
+
+  if [[ $# -eq 0 ]]; then
+    # Without a command open an interactive login shell in the guest
+    # shellcheck disable=SC2016
+    remote_cmd+=' && exec "${SHELL:-/bin/sh}" -l'
+  else
+    remote_cmd+=" &&"
+    for arg in "$@"; do
+      remote_cmd+=" $(shell_quote "$arg")"
+    done
+  fi
+
   if [[ -t 1 ]] && [[ -t 2 ]]; then
     tty_flag='-t'
   else
     tty_flag='-T'
   fi
 
-  exec dbclient "${tty_flag}" -q -p 10022 -y root@localhost "cd $(pwd); ${envs} ${*}"
+  exec dbclient "${tty_flag}" -q -p 10022 -y root@localhost "$remote_cmd"
 fi
@@ -10,14 +10,18 @@ bin="${1:?The executable should be present}"
 shift
 
 triple="${CARGO_GUNGRAUN_TARGET:?The build target triple should be present}"
-runner="${GUNGRAUN_RUNNER:?The GUNGRAUN_RUNNER variable should be set}"
 qemu_arch="$(qemu_architecture "${triple}")"
 
-if version="$("$runner" --version)"; then
-  version="$(cut -d' ' -f2 - <<<"$version")"
-else
-  version=0.1.0
-fi
+# Benches using another gungraun version than the rest of the workspace have their own runner.
+# The file name of the bench executable is the name of the bench and a hash.
+bench_name="$(basename "$bin")"
+bench_name="${bench_name%-*}"
+read -r -a runners <<<"${CARGO_GUNGRAUN_RUNNERS:-}"
+for entry in "${runners[@]}"; do
+  if [[ "${entry%%=*}" == "$bench_name" ]]; then
+    export GUNGRAUN_RUNNER="${entry#*=}"
+  fi
+done
 
 args=("--qemu-arch" "${triple}")
 if is_trace; then
@@ -40,20 +44,75 @@ qemu_cmd=("qemu-$qemu_arch")
 qemu_runner_cmd=('/qemu_runner.sh')
 qemu_runner_cmd+=("${args[@]}")
 
-if "${qemu_cmd[@]}" "$bin" --gungraun-run invalid |& grep -q "function.*invalid.*not found in this scope"; then
-  if printf '0.17.1\n%s' "$version" | sort -V | head -1 | grep -q '0\.17\.1'; then
-    # Start qemu before running the benchmark to support running multiple
-    # benchmarks in parallel if the benchmark harness allows it
-    "${qemu_runner_cmd[@]}" --no-run --
-    exec "${qemu_cmd[@]}" "$bin" "$@"
+# Record how the bench was run and its exit code for the summary printed by cargo-gungraun
+#
+# Parameters:
+#   $1: Where the bench ran: gungraun, qemu-user, vm, skip or error
+#   $2: The exit code of the bench
+record() {
+  if [[ -n "$CARGO_GUNGRAUN_SUMMARY_FILE" ]]; then
+    printf '%s\t%s\t%s\t%s\n' "$bench_name" "$harness" "$1" "$2" >>"$CARGO_GUNGRAUN_SUMMARY_FILE"
+  fi
+}
+
+# The harness of the bench targets was classified by cargo-gungraun from the cargo metadata.
+# Other executables like the lib and bin targets and benches with a custom harness are probed.
+harness=""
+read -r -a benches <<<"${CARGO_GUNGRAUN_BENCHES:-}"
+for entry in "${benches[@]}"; do
+  if [[ "${entry%%=*}" == "$bench_name" ]]; then
+    harness="${entry#*=}"
+  fi
+done
+
+if [[ -z "$harness" || "$harness" == "other" ]]; then
+  # The versions of gungraun and the runner are checked by cargo-gungraun before the container
+  # is started
+  if "${qemu_cmd[@]}" "$bin" --gungraun-run invalid |& grep -q "function.*invalid.*not found in this scope"; then
+    harness="gungraun"
   else
-    echo "runner.sh: Error: cargo-gungraun needs a gungraun version >= 0.17.1" >&2
-    exit 1
+    harness="${harness:-unknown}"
   fi
-elif "${qemu_cmd[@]}" "$bin" --iai-run invalid |& grep -q "function.*invalid.*not found in this scope"; then
-  echo "runner.sh: Error: iai-callgrind is not supported by cargo-gungraun. Please update to gungraun >= 0.17.1" >&2
-  exit 1
-else
+fi
+
+# The exit code of the bench is recorded and returned unchanged
+status=0
+if [[ "$harness" == "gungraun" ]]; then
+  # Start qemu before running the benchmark to support running multiple
+  # benchmarks in parallel if the benchmark harness allows it
   "${qemu_runner_cmd[@]}" --no-run --
-  exec "${qemu_runner_cmd[@]}" -- "$bin" "$@"
+  "${qemu_cmd[@]}" "$bin" "$@" || status=$?
+  record "gungraun" "$status"
+  exit "$status"
 fi
+
+# The policy only applies to `cargo gungraun bench` which sets the benches even if there are none.
+# Other executables, for example from `cargo gungraun exec -- cargo test`, run in the qemu guest.
+if [[ -n "${CARGO_GUNGRAUN_BENCHES+x}" ]]; then
+  policy="${CARGO_GUNGRAUN_NON_GUNGRAUN:-skip}"
+else
+  policy="run-vm"
+fi
+
+case "$policy" in
+run-vm)
+  "${qemu_runner_cmd[@]}" --no-run --
+  "${qemu_runner_cmd[@]}" -- "$bin" "$@" || status=$?
+  record "vm" "$status"
+  exit "$status"
+  ;;
+run-qemu-user)
+  "${qemu_cmd[@]}" "$bin" "$@" || status=$?
+  record "qemu-user" "$status"
+  exit "$status"
+  ;;
+error)
+  record "error" 1
+  bail "'$bench_name' ($harness) doesn't use gungraun and the policy is 'error'"
+  ;;
+*)
+  record "skip" 0
+  echo "Skipping '$bench_name' ($harness) which doesn't use gungraun" >&2
+  exit 0
+  ;;
+esac
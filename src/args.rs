@@ -9,6 +9,7 @@ use anyhow::{anyhow, Context, Result};
 use camino::Utf8PathBuf;
 use colored::Colorize;
 
+use crate::meta::{HarnessPolicy, Selection};
 use crate::{cargo_bin, envs, Target};
 
 // TODO: List, Test, Version, HelpAll (to print help of gungraun)
@@ -17,7 +18,30 @@ pub enum Command {
     /// Benchmarks
     #[default]
     Bench,
+    /// Remove stale containers and generated files
+    Clean {
+        /// Also remove outdated images
+        images: bool,
+    },
+    /// Check the setup
+    Doctor,
+    /// Stop the kept container
+    Down,
+    /// Run an arbitrary command in the container
+    Exec {
+        /// The command with its arguments
+        argv: Vec<OsString>,
+    },
     Help,
+    /// Print the embedded seccomp profile
+    Seccomp,
+    /// Open an interactive shell in the container or guest
+    Shell {
+        /// Open the shell in the qemu guest
+        guest: bool,
+    },
+    /// Start a kept container and boot the guest
+    Up,
     Version,
 }
 
@@ -25,6 +49,13 @@ impl Command {
     fn parse(value: &OsStr) -> Result<Self> {
         match value.to_str() {
             Some("bench") => Ok(Self::Bench),
+            Some("clean") => Ok(Self::Clean { images: false }),
+            Some("doctor") => Ok(Self::Doctor),
+            Some("down") => Ok(Self::Down),
+            Some("exec") => Ok(Self::Exec { argv: vec![] }),
+            Some("seccomp") => Ok(Self::Seccomp),
+            Some("shell") => Ok(Self::Shell { guest: false }),
+            Some("up") => Ok(Self::Up),
             Some("help") => Ok(Self::Help),
             Some(unknown) => Err(anyhow!("Unexpected command: {unknown}")),
             None => Err(anyhow!("Invalid arg: '{value:?}'")),
@@ -37,15 +68,85 @@ pub struct Args {
     pub cargo: Vec<OsString>,
     pub color: Color,
     pub command: Command,
+    pub env_file: Option<Utf8PathBuf>,
     pub help: bool,
+    pub keep: bool,
+    /// The `--manifest-path` which is not passed through to cargo
+    pub manifest_path: Option<Utf8PathBuf>,
+    /// What to do with benches which don't use gungraun
+    pub non_gungraun: Option<HarnessPolicy>,
+    pub offline: bool,
+    /// The packages and benches selected with `-p` and `--bench`
+    pub selection: Selection,
     pub target: Option<Target>,
     pub target_dir: Option<Utf8PathBuf>,
+    /// The toolchain from a leading `+toolchain` without the `+`
+    pub toolchain: Option<String>,
 }
 
 impl Args {
     pub fn print_command_help(&self) {
-        println!("This is the command help");
-        // TODO: implement
+        println!(
+            "Run gungraun benchmarks on foreign targets via podman/docker
+
+{} {}
+
+The toolchain is selected like rustup does: `+TOOLCHAIN`, RUSTUP_TOOLCHAIN, a directory override,
+a rust-toolchain.toml file or the default toolchain. The standard library of the target is
+installed for this toolchain, which is also used in the container.
+
+{}
+  bench  Run the benchmarks (the default). With `--keep`, keep the container running for
+         reuse by later invocations
+  clean  Stop stale containers and remove generated files. With `--images`, also remove
+         outdated cargo-gungraun images
+  shell  Open an interactive shell in the container. With `--guest`, open the shell in the
+         booted qemu guest
+  up     Start a kept container for the target and boot the guest
+  down   Stop the kept container of the target
+  doctor Check the container engine, the workspace and the gungraun-runner. With `--target`,
+         also check the image and the standard library of the target
+  exec   Run a command in the container: `cargo gungraun exec [OPTIONS] [--] <CMD>...`
+  seccomp
+         Print the embedded seccomp profile including the syscalls allowed with
+         CARGO_GUNGRAUN_SECCOMP_SYSCALLS
+  help   Print this help message
+
+{}
+  --env-file=<PATH>
+         Forward the variables of the dotenv file into the container and the guest. Defaults
+         to CARGO_GUNGRAUN_ENV_FILE
+  --keep Keep the container running for reuse by later invocations
+  --manifest-path <PATH>
+         The Cargo.toml of the package to benchmark. Allows running cargo-gungraun from outside
+         the workspace
+  --non-gungraun=<POLICY>
+         What to do with benches which don't use gungraun, like criterion or libtest benches:
+         skip (the default), run-qemu-user, run-vm or error. Defaults to
+         CARGO_GUNGRAUN_NON_GUNGRAUN or `non-gungraun` in [workspace.metadata.gungraun]
+  --offline
+         Run without network access. Fails early if the image, the standard library of the
+         target or the gungraun-runner is missing
+
+{}
+  0      Success
+  1      Any other error
+  2      Invalid command line arguments or usage
+  3      Performance regressions detected by gungraun
+  10     Invalid configuration in the environment or the manifest
+  11     The container engine was not found or is not reachable
+  12     The image is not available
+  13     The bootstrap script of the container failed
+  14     Booting the qemu guest failed
+  15     Building the benches failed
+  16     The toolchain or the standard library of the target is not available
+  If a bench fails, its own exit code is used unchanged",
+            "Usage:".blue().bold(),
+            "cargo gungraun [+TOOLCHAIN] [COMMAND] [OPTIONS]".bright_blue(),
+            "Commands:".blue().bold(),
+            "Options:".blue().bold(),
+            "Exit status:".blue().bold(),
+        );
     }
 
     /// TODO: DOCS
@@ -130,11 +231,17 @@ impl Display for Color {
     }
 }
 
-/// TODO:
+/// Parse the command line `args` which start with the path of the executable
 ///
 /// # Errors
-pub fn parse(color: Option<Color>) -> Result<Args> {
-    let raw = std::env::args_os().skip(1);
+///
+/// If an argument is invalid
+#[allow(clippy::too_many_lines)]
+pub fn parse<I>(args: I, color: Option<Color>) -> Result<Args>
+where
+    I: IntoIterator<Item = OsString>,
+{
+    let raw = args.into_iter().skip(1);
     let target = match std::env::var(envs::CARGO_BUILD_TARGET) {
         Ok(value) => Target::parse(Some(&value)),
         Err(_) => Ok(None),
@@ -159,17 +266,47 @@ pub fn parse(color: Option<Color>) -> Result<Args> {
         target_dir,
         cargo: vec![],
         command: Command::default(),
+        env_file: None,
         help: false,
+        keep: false,
+        manifest_path: None,
+        non_gungraun: None,
+        offline: false,
+        selection: Selection::default(),
+        toolchain: None,
     };
 
     let mut is_command = true;
 
     let raw = clap_lex::RawArgs::new(raw);
     let mut cursor = raw.cursor();
-    raw.next(&mut cursor); // Skip the bin
+    raw.next(&mut cursor); // Skip the `gungraun` of `cargo gungraun`
+    if let Some(toolchain) = raw
+        .peek(&cursor)
+        .and_then(|arg| arg.to_value().ok())
+        .and_then(|value| value.strip_prefix('+'))
+    {
+        if toolchain.is_empty() {
+            return Err(anyhow!("A toolchain name is required after '+'"));
+        }
+        args.toolchain = Some(toolchain.to_owned());
+        raw.next(&mut cursor);
+    }
     while let Some(arg) = raw.next(&mut cursor) {
         args.cargo.push(arg.to_value_os().to_os_string());
 
+        if let Command::Exec { argv } = &mut args.command {
+            // The first positional argument or everything after `--` is the command to execute
+            if arg.is_escape() || !arg.is_long() && !arg.is_short() {
+                args.cargo.pop();
+                if !arg.is_escape() {
+                    argv.push(arg.to_value_os().to_os_string());
+                }
+                argv.extend(raw.remaining(&mut cursor).map(OsStr::to_os_string));
+                break;
+            }
+        }
+
         if arg.is_escape() {
             args.cargo
                 .extend(raw.remaining(&mut cursor).map(OsStr::to_os_string));
@@ -188,10 +325,75 @@ pub fn parse(color: Option<Color>) -> Result<Args> {
                         .with_context(|| format!("Invalid --{flag}"))?;
                     args.target_dir = Some(path);
                 }
+                Ok(flag @ "env-file") => {
+                    let value = value.ok_or_else(|| anyhow!("A value is required for --{flag}"))?;
+                    let path = Utf8PathBuf::try_from(value.to_os_string())
+                        .with_context(|| format!("Invalid --{flag}"))?;
+                    args.env_file = Some(path);
+                    args.cargo.pop();
+                }
+                Ok("guest") => {
+                    if let Command::Shell { guest } = &mut args.command {
+                        *guest = true;
+                        args.cargo.pop();
+                    }
+                }
                 Ok("help") => {
                     args.help = true;
                     return Ok(args);
                 }
+                Ok("images") => {
+                    if let Command::Clean { images } = &mut args.command {
+                        *images = true;
+                        args.cargo.pop();
+                    }
+                }
+                Ok("keep") => {
+                    args.keep = true;
+                    args.cargo.pop();
+                }
+                Ok(flag @ "manifest-path") => {
+                    args.cargo.pop();
+                    let value = match value {
+                        Some(value) => value.to_os_string(),
+                        None => raw
+                            .next_os(&mut cursor)
+                            .map(OsStr::to_os_string)
+                            .ok_or_else(|| anyhow!("A value is required for --{flag}"))?,
+                    };
+                    let path =
+                        Utf8PathBuf::try_from(value).with_context(|| format!("Invalid --{flag}"))?;
+                    args.manifest_path = Some(path);
+                }
+                Ok(flag @ "non-gungraun") => {
+                    let value = value
+                        .and_then(|v| v.to_str())
+                        .ok_or_else(|| anyhow!("A value is required for --{flag}"))?;
+                    args.non_gungraun =
+                        Some(value.parse().with_context(|| format!("Invalid --{flag}"))?);
+                    args.cargo.pop();
+                }
+                Ok("offline") => {
+                    // Also passed through to cargo
+                    args.offline = true;
+                }
+                Ok(flag @ ("bench" | "package")) => {
+                    // Also passed through to cargo
+                    let value = match value {
+                        Some(value) => Some(value.to_os_string()),
+                        None => next_value(&raw, &mut cursor, &mut args.cargo),
+                    };
+                    if let Some(value) = value {
+                        let value = value
+                            .into_string()
+                            .map_err(|_| anyhow!("Invalid --{flag}: Not utf8"))?;
+                        if flag == "bench" {
+                            args.selection.benches.push(value);
+                        } else {
+                            args.selection.packages.push(value);
+                        }
+                    }
+                }
                 _ => {}
             }
         } else if let Some(mut shorts) = arg.to_short() {
@@ -205,6 +407,20 @@ pub fn parse(color: Option<Color>) -> Result<Args> {
                         args.help = true;
                         return Ok(args);
                     }
+                    Ok('p') => {
+                        // Also passed through to cargo
+                        let value = match shorts.next_value_os() {
+                            Some(value) => Some(value.to_os_string()),
+                            None => next_value(&raw, &mut cursor, &mut args.cargo),
+                        };
+                        if let Some(value) = value {
+                            args.selection.packages.push(
+                                value
+                                    .into_string()
+                                    .map_err(|_| anyhow!("Invalid -p: Not utf8"))?,
+                            );
+                        }
+                    }
                     _ => {}
                 }
             }
@@ -221,6 +437,24 @@ pub fn parse(color: Option<Color>) -> Result<Args> {
     Ok(args)
 }
 
+/// Take the value of an option given as the separate next argument
+///
+/// The value is also pushed to the `cargo` arguments.
+fn next_value(
+    raw: &clap_lex::RawArgs,
+    cursor: &mut clap_lex::ArgCursor,
+    cargo: &mut Vec<OsString>,
+) -> Option<OsString> {
+    let next = raw.peek(cursor)?;
+    if next.is_escape() || next.is_long() || next.is_short() {
+        return None;
+    }
+
+    let value = raw.next_os(cursor)?.to_os_string();
+    cargo.push(value.clone());
+    Some(value)
+}
+
 #[cfg(test)]
 mod tests {
     use rstest::rstest;
@@ -250,6 +484,68 @@ mod tests {
         assert_eq!(Target::parse(Some(&from)).unwrap(), Some(expected));
     }
 
+    fn parse_args(args: &[&str]) -> Args {
+        parse(
+            ["cargo-gungraun", "gungraun"]
+                .iter()
+                .chain(args)
+                .map(OsString::from),
+            None,
+        )
+        .unwrap()
+    }
+
+    #[rstest]
+    #[case::with_command(&["+nightly", "bench"], &[])]
+    #[case::without_command(&["+nightly"], &[])]
+    #[case::with_cargo_args(&["+1.80", "bench", "--bench", "b"], &["--bench", "b"])]
+    fn parse_toolchain(#[case] args: &[&str], #[case] cargo: &[&str]) {
+        let parsed = parse_args(args);
+
+        assert_eq!(parsed.toolchain.as_deref(), Some(&args[0][1..]), "{args:?}");
+        assert!(matches!(parsed.command, Command::Bench));
+        assert_eq!(parsed.cargo, cargo);
+    }
+
+    #[test]
+    fn parse_when_toolchain_is_empty() {
+        assert_eq!(
+            parse(
+                ["cargo-gungraun", "gungraun", "+"].map(OsString::from),
+                None
+            )
+            .unwrap_err()
+            .to_string(),
+            "A toolchain name is required after '+'"
+        );
+    }
+
+    #[rstest]
+    #[case::separate(&["bench", "--manifest-path", "x/Cargo.toml", "--bench", "b"])]
+    #[case::equals(&["bench", "--manifest-path=x/Cargo.toml", "--bench", "b"])]
+    fn parse_manifest_path(#[case] args: &[&str]) {
+        let parsed = parse_args(args);
+
+        assert_eq!(
+            parsed.manifest_path,
+            Some(Utf8PathBuf::from("x/Cargo.toml"))
+        );
+        assert_eq!(parsed.cargo, ["--bench", "b"]);
+        assert_eq!(parsed.selection.benches, ["b"]);
+    }
+
+    #[test]
+    fn parse_exec_argv() {
+        let parsed = parse_args(&["exec", "--offline", "--", "cmd", "--flag"]);
+
+        let Command::Exec { argv } = parsed.command else {
+            panic!("Expected the exec command but was {:?}", parsed.command);
+        };
+        assert_eq!(argv, ["cmd", "--flag"]);
+        assert_eq!(parsed.cargo, ["--offline"]);
+        assert!(parsed.offline);
+    }
+
     #[test]
     fn target_parse_when_invalid() {
         assert_eq!(
@@ -0,0 +1,357 @@
+//! The compatibility checks of gungraun, iai-callgrind and the gungraun-runner
+//!
+//! The checks run on the host before any container is started.
+
+use core::cmp::Ordering;
+use core::fmt::Display;
+use core::str::FromStr;
+use std::process::Stdio;
+
+use anyhow::{anyhow, Result};
+use camino::Utf8Path;
+use log::{debug, warn};
+
+use crate::envs;
+use crate::error::Error;
+use crate::meta::CargoMetadata;
+
+/// The minimum supported gungraun version
+pub const MIN_GUNGRAUN_VERSION: Version = Version::new(0, 17, 1);
+
+/// A semantic version
+///
+/// Build metadata is ignored and any pre-release is lower than the release.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Version {
+    major: u64,
+    minor: u64,
+    patch: u64,
+    pre: Option<String>,
+}
+
+impl Version {
+    /// Create a new release version
+    #[must_use]
+    pub const fn new(major: u64, minor: u64, patch: u64) -> Self {
+        Self {
+            major,
+            minor,
+            patch,
+            pre: None,
+        }
+    }
+}
+
+impl Display for Version {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        write!(f, "{}.{}.{}", self.major, self.minor, self.patch)?;
+        if let Some(pre) = &self.pre {
+            write!(f, "-{pre}")?;
+        }
+        Ok(())
+    }
+}
+
+impl FromStr for Version {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let version = s.split_once('+').map_or(s, |(version, _)| version);
+        let (version, pre) = match version.split_once('-') {
+            Some((version, pre)) => (version, Some(pre.to_owned())),
+            None => (version, None),
+        };
+
+        let mut parts = version.split('.').map(str::parse::<u64>);
+        match (parts.next(), parts.next(), parts.next(), parts.next()) {
+            (Some(Ok(major)), Some(Ok(minor)), Some(Ok(patch)), None) => Ok(Self {
+                major,
+                minor,
+                patch,
+                pre,
+            }),
+            _ => Err(anyhow!("Invalid version: '{s}'")),
+        }
+    }
+}
+
+impl Ord for Version {
+    fn cmp(&self, other: &Self) -> Ordering {
+        (self.major, self.minor, self.patch)
+            .cmp(&(other.major, other.minor, other.patch))
+            .then_with(|| match (&self.pre, &other.pre) {
+                (None, None) => Ordering::Equal,
+                (None, Some(_)) => Ordering::Greater,
+                (Some(_), None) => Ordering::Less,
+                (Some(a), Some(b)) => a.cmp(b),
+            })
+    }
+}
+
+impl PartialOrd for Version {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+/// Check the gungraun and iai-callgrind packages of the `metadata` and the `runner`
+///
+/// The `runner` is the gungraun-runner from [`envs::GUNGRAUN_RUNNER`] if set.
+///
+/// # Errors
+///
+/// If anything is incompatible. The [`Error::Config`] lists all problems with upgrade hints
+pub fn check(metadata: &CargoMetadata, runner: Option<&Utf8Path>) -> Result<()> {
+    let mut problems = check_packages(metadata);
+
+    if let Some(runner) = runner {
+        if let Some(version) = metadata.gungraun_version() {
+            problems.extend(check_runner(runner, &version));
+        }
+    }
+
+    if problems.is_empty() {
+        Ok(())
+    } else {
+        let mut message = "Incompatible gungraun setup:".to_owned();
+        for problem in problems {
+            message.push_str("\n  - ");
+            message.push_str(&problem);
+        }
+        Err(Error::Config(message).into())
+    }
+}
+
+/// Check the versions of all gungraun packages and the usage of iai-callgrind
+#[must_use]
+pub fn check_packages(metadata: &CargoMetadata) -> Vec<String> {
+    let mut problems = vec![];
+
+    for package in metadata.packages.iter().filter(|p| p.name == "gungraun") {
+        match package.version.parse::<Version>() {
+            Ok(version) if version >= MIN_GUNGRAUN_VERSION => {
+                debug!("Found compatible gungraun {version}");
+            }
+            Ok(version) => problems.push(format!(
+                "gungraun {version} is not supported{}. Update to gungraun >= \
+                 {MIN_GUNGRAUN_VERSION} with `cargo update -p gungraun` or by raising the version \
+                 requirement",
+                dependents(metadata, "gungraun")
+            )),
+            Err(error) => problems.push(format!("gungraun: {error}")),
+        }
+    }
+
+    if metadata.packages.iter().any(|p| p.name == "iai-callgrind") {
+        problems.push(format!(
+            "iai-callgrind is not supported{}. Migrate to gungraun >= {MIN_GUNGRAUN_VERSION}, the \
+             successor of iai-callgrind",
+            dependents(metadata, "iai-callgrind")
+        ));
+    }
+
+    problems
+}
+
+/// Check that the gungraun-runner at `path` has the same version as gungraun
+///
+/// A runner which can't be executed on the host is not checked.
+#[must_use]
+pub fn check_runner(path: &Utf8Path, gungraun_version: &str) -> Option<String> {
+    let output = match std::process::Command::new(path)
+        .arg("--version")
+        .stderr(Stdio::null())
+        .output()
+    {
+        Ok(output) if output.status.success() => output,
+        Ok(_) | Err(_) => {
+            warn!("Unable to determine the version of the gungraun-runner '{path}'. Skipping check");
+            return None;
+        }
+    };
+
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    match parse_runner_version(&stdout) {
+        Some(version) if version == gungraun_version => None,
+        Some(version) => Some(format!(
+            "The gungraun-runner '{path}' from {} has version {version} but gungraun has version \
+             {gungraun_version}. Use the gungraun-runner {gungraun_version} or unset {} to \
+             download it",
+            envs::GUNGRAUN_RUNNER,
+            envs::GUNGRAUN_RUNNER
+        )),
+        None => Some(format!(
+            "The gungraun-runner '{path}' from {} printed an unexpected version: '{}'",
+            envs::GUNGRAUN_RUNNER,
+            stdout.trim()
+        )),
+    }
+}
+
+/// Parse the version from the output of `gungraun-runner --version`
+#[must_use]
+pub fn parse_runner_version(output: &str) -> Option<&str> {
+    match output.split_whitespace().collect::<Vec<_>>().as_slice() {
+        ["gungraun-runner", version] => Some(version),
+        _ => None,
+    }
+}
+
+/// The list of the packages depending on `name` for the messages
+fn dependents(metadata: &CargoMetadata, name: &str) -> String {
+    let dependents = metadata
+        .packages
+        .iter()
+        .filter_map(|package| {
+            package
+                .dependencies
+                .iter()
+                .find(|d| d.name == name)
+                .map(|d| format!("{} ({name} {})", package.name, d.req))
+        })
+        .collect::<Vec<_>>();
+
+    if dependents.is_empty() {
+        String::new()
+    } else {
+        format!(" (required by {})", dependents.join(", "))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::PathBuf;
+
+    use rstest::rstest;
+
+    use super::*;
+    use crate::meta::{Dependency, Package};
+
+    fn package(name: &str, version: &str, dependencies: &[(&str, &str)]) -> Package {
+        Package {
+            dependencies: dependencies
+                .iter()
+                .map(|(name, req)| Dependency {
+                    kind: None,
+                    name: (*name).to_owned(),
+                    optional: false,
+                    rename: None,
+                    req: (*req).to_owned(),
+                })
+                .collect(),
+            id: format!("{name} {version}"),
+            manifest_path: PathBuf::from(format!("/{name}/Cargo.toml")),
+            name: name.to_owned(),
+            version: version.to_owned(),
+            ..Package::default()
+        }
+    }
+
+    fn metadata(packages: Vec<Package>) -> CargoMetadata {
+        CargoMetadata {
+            packages,
+            target_directory: PathBuf::from("/target"),
+            workspace_root: PathBuf::from("/"),
+            ..CargoMetadata::default()
+        }
+    }
+
+    #[rstest]
+    #[case::release("0.17.1", Version::new(0, 17, 1))]
+    #[case::build("1.2.3+build", Version::new(1, 2, 3))]
+    #[case::pre("0.18.0-rc.1", Version { pre: Some("rc.1".to_owned()), ..Version::new(0, 18, 0) })]
+    fn version_parse_when_valid(#[case] input: &str, #[case] expected: Version) {
+        assert_eq!(input.parse::<Version>().unwrap(), expected);
+    }
+
+    #[rstest]
+    #[case::two_parts("0.17")]
+    #[case::four_parts("0.17.1.2")]
+    #[case::not_a_number("0.x.1")]
+    fn version_parse_when_invalid(#[case] input: &str) {
+        assert_eq!(
+            input.parse::<Version>().unwrap_err().to_string(),
+            format!("Invalid version: '{input}'")
+        );
+    }
+
+    #[rstest]
+    #[case::equal("0.17.1", "0.17.1", Ordering::Equal)]
+    #[case::patch("0.17.2", "0.17.1", Ordering::Greater)]
+    #[case::minor("0.16.9", "0.17.1", Ordering::Less)]
+    #[case::numeric("0.100.0", "0.17.1", Ordering::Greater)]
+    #[case::pre("0.17.1-rc.1", "0.17.1", Ordering::Less)]
+    fn version_cmp(#[case] a: &str, #[case] b: &str, #[case] expected: Ordering) {
+        assert_eq!(
+            a.parse::<Version>()
+                .unwrap()
+                .cmp(&b.parse::<Version>().unwrap()),
+            expected
+        );
+    }
+
+    #[test]
+    fn check_packages_when_compatible() {
+        let metadata = metadata(vec![
+            package("bench", "0.1.0", &[("gungraun", "^0.18")]),
+            package("gungraun", "0.18.0", &[]),
+        ]);
+
+        assert!(check_packages(&metadata).is_empty());
+    }
+
+    #[test]
+    fn check_packages_when_too_old() {
+        let metadata = metadata(vec![
+            package("bench", "0.1.0", &[("gungraun", "^0.16")]),
+            package("gungraun", "0.16.0", &[]),
+        ]);
+
+        assert_eq!(
+            check_packages(&metadata),
+            [
+                "gungraun 0.16.0 is not supported (required by bench (gungraun ^0.16)). Update to \
+                 gungraun >= 0.17.1 with `cargo update -p gungraun` or by raising the version \
+                 requirement"
+            ]
+        );
+    }
+
+    #[test]
+    fn check_when_incompatible() {
+        let metadata = metadata(vec![
+            package("bench", "0.1.0", &[("gungraun", "^0.16")]),
+            package("gungraun", "0.16.0", &[]),
+        ]);
+
+        let error = check(&metadata, None).unwrap_err();
+        assert_eq!(Error::exit_code_of(&error), 10);
+        assert!(error
+            .to_string()
+            .starts_with("Incompatible gungraun setup:\n  - gungraun 0.16.0 is not supported"));
+    }
+
+    #[test]
+    fn check_packages_when_iai_callgrind() {
+        let metadata = metadata(vec![
+            package("bench", "0.1.0", &[("iai-callgrind", "^0.14")]),
+            package("iai-callgrind", "0.14.0", &[]),
+        ]);
+
+        assert_eq!(
+            check_packages(&metadata),
+            [
+                "iai-callgrind is not supported (required by bench (iai-callgrind ^0.14)). Migrate \
+                 to gungraun >= 0.17.1, the successor of iai-callgrind"
+            ]
+        );
+    }
+
+    #[rstest]
+    #[case::valid("gungraun-runner 0.18.0\n", Some("0.18.0"))]
+    #[case::other_name("iai-callgrind-runner 0.14.0", None)]
+    #[case::empty("", None)]
+    fn parse_runner_version_when(#[case] output: &str, #[case] expected: Option<&str>) {
+        assert_eq!(parse_runner_version(output), expected);
+    }
+}
@@ -0,0 +1,904 @@
+//! A client for the Docker compatible REST API of the container engines
+//!
+//! Docker and podman both serve this API over a local unix socket. The client speaks plain
+//! HTTP/1.1 with one connection per request.
+
+use std::collections::HashMap;
+use std::io::{self, BufRead, BufReader, Read, Write};
+use std::os::unix::net::UnixStream;
+use std::path::PathBuf;
+
+use anyhow::{anyhow, Context, Result};
+use log::{debug, info};
+use serde::Deserialize;
+use serde_json::{json, Value};
+
+use super::seccomp::SeccompProfile;
+use super::{ContainerState, Engine, ExecSpec, RunSpec};
+use crate::envs;
+use crate::error::Error;
+
+/// The api version prefix of all requests
+///
+/// Podman's compatibility layer supports this version, too.
+const API_VERSION: &str = "v1.41";
+
+/// The client for the REST API of a container engine
+#[derive(Debug, Clone)]
+pub struct Client {
+    socket: PathBuf,
+}
+
+/// The response of a request which has been read completely
+#[derive(Debug)]
+pub struct Response {
+    /// The body of the response with the transfer encoding already removed
+    pub body: Vec<u8>,
+    /// The http status code
+    pub status: u16,
+}
+
+/// A reader for a response body in `chunked` transfer encoding
+#[derive(Debug)]
+struct ChunkedReader<R> {
+    done: bool,
+    inner: R,
+    remaining: usize,
+}
+
+/// The error message in the body of failed requests
+#[derive(Debug, Deserialize)]
+struct ErrorMessage {
+    message: String,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "PascalCase")]
+struct ExecInspect {
+    exit_code: Option<i32>,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "PascalCase")]
+struct IdResponse {
+    id: String,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "PascalCase")]
+struct Inspect {
+    config: InspectConfig,
+    state: InspectState,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "PascalCase")]
+struct InspectConfig {
+    labels: Option<HashMap<String, String>>,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "PascalCase")]
+struct InspectState {
+    running: bool,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "PascalCase")]
+struct WaitResponse {
+    status_code: i64,
+}
+
+impl<R: BufRead> ChunkedReader<R> {
+    const fn new(inner: R) -> Self {
+        Self {
+            done: false,
+            inner,
+            remaining: 0,
+        }
+    }
+
+    fn read_size(&mut self) -> io::Result<usize> {
+        let mut line = String::new();
+        self.inner.read_line(&mut line)?;
+        let size = line.trim().split(';').next().unwrap_or_default();
+        usize::from_str_radix(size, 16)
+            .map_err(|error| io::Error::new(io::ErrorKind::InvalidData, error))
+    }
+}
+
+impl<R: BufRead> Read for ChunkedReader<R> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        if self.done || buf.is_empty() {
+            return Ok(0);
+        }
+
+        if self.remaining == 0 {
+            self.remaining = self.read_size()?;
+            if self.remaining == 0 {
+                self.done = true;
+                return Ok(0);
+            }
+        }
+
+        let max = buf.len().min(self.remaining);
+        let read = self.inner.read(&mut buf[..max])?;
+        if read == 0 {
+            return Err(io::ErrorKind::UnexpectedEof.into());
+        }
+
+        self.remaining -= read;
+        if self.remaining == 0 {
+            // Every chunk is terminated by CRLF
+            let mut crlf = [0; 2];
+            self.inner.read_exact(&mut crlf)?;
+        }
+
+        Ok(read)
+    }
+}
+
+impl Client {
+    /// Create a new client connecting to the unix `socket`
+    #[must_use]
+    pub const fn new(socket: PathBuf) -> Self {
+        Self { socket }
+    }
+
+    /// Create a new client for the `engine` with the socket from the environment
+    ///
+    /// The socket is [`envs::CARGO_GUNGRAUN_ENGINE_SOCKET`] if present. Otherwise, it is
+    /// taken from `CONTAINER_HOST` (podman) or `DOCKER_HOST` (docker) if these point to a unix
+    /// socket and falls back to the default socket location of the engine.
+    ///
+    /// # Errors
+    ///
+    /// If the engine doesn't provide a REST API
+    pub fn from_env(engine: Engine) -> Result<Self> {
+        if let Some(socket) = std::env::var_os(envs::CARGO_GUNGRAUN_ENGINE_SOCKET) {
+            return Ok(Self::new(socket.into()));
+        }
+
+        let host_env = match engine {
+            Engine::Podman | Engine::PodmanRemote => "CONTAINER_HOST",
+            Engine::Docker => "DOCKER_HOST",
+            Engine::Nerdctl => {
+                return Err(anyhow!(
+                    "The API backend is not supported by nerdctl. Please use the cli backend"
+                ))
+            }
+        };
+        if let Some(socket) = std::env::var(host_env)
+            .ok()
+            .and_then(|host| host.strip_prefix("unix://").map(PathBuf::from))
+        {
+            return Ok(Self::new(socket));
+        }
+
+        let socket = if engine == Engine::Docker {
+            PathBuf::from("/var/run/docker.sock")
+        } else {
+            std::env::var_os("XDG_RUNTIME_DIR")
+                .map(|dir| PathBuf::from(dir).join("podman/podman.sock"))
+                .filter(|path| path.exists())
+                .unwrap_or_else(|| PathBuf::from("/run/podman/podman.sock"))
+        };
+
+        Ok(Self::new(socket))
+    }
+
+    /// Create and start a container with the [`RunSpec`]
+    ///
+    /// A missing image is pulled first if [`RunSpec::pull`] is true.
+    ///
+    /// # Errors
+    ///
+    /// If the image is missing and can't be pulled or the container could not be created or
+    /// started
+    pub fn create(&self, spec: &RunSpec) -> Result<()> {
+        if !self.has_image(&spec.image)? {
+            if !spec.pull {
+                return Err(Error::ImageMissing(format!(
+                    "The image '{}' is not available locally",
+                    spec.image
+                ))
+                .into());
+            }
+            self.pull(&spec.image)?;
+        }
+
+        let mut security_opts = vec![];
+        match &spec.seccomp {
+            SeccompProfile::Default => {}
+            SeccompProfile::File(path) => {
+                // The api expects the content of the profile instead of the path
+                let profile = std::fs::read_to_string(path)
+                    .with_context(|| format!("Failed to read the seccomp profile '{path}'"))?;
+                security_opts.push(format!("seccomp={profile}"));
+            }
+            SeccompProfile::Unconfined => security_opts.push("seccomp=unconfined".to_owned()),
+        }
+        security_opts.extend(spec.security_opts.iter().cloned());
+
+        let mut host_config = json!({
+            "Binds": spec.volumes,
+            "Privileged": spec.privileged,
+            "SecurityOpt": security_opts,
+        });
+        if let Some(userns) = &spec.userns {
+            host_config["UsernsMode"] = json!(userns);
+        }
+
+        let body = json!({
+            "Cmd": spec.cmd,
+            "Env": spec.env,
+            "HostConfig": host_config,
+            "Image": spec.image,
+            "Labels": spec.labels.iter().cloned().collect::<HashMap<_, _>>(),
+            "User": spec.user,
+        });
+
+        self.request(
+            "POST",
+            &format!("/containers/create?name={}", spec.name),
+            Some(&body),
+        )?
+        .check()?;
+        self.request("POST", &format!("/containers/{}/start", spec.name), None)?
+            .check()?;
+
+        Ok(())
+    }
+
+    /// Run the command of the [`ExecSpec`] in the container `name`
+    ///
+    /// The output of the command is streamed to the stdout and stderr of this process.
+    ///
+    /// # Errors
+    ///
+    /// If the command could not be executed or didn't exit successfully
+    pub fn exec(&self, name: &str, spec: &ExecSpec) -> Result<()> {
+        let argv = spec
+            .argv
+            .iter()
+            .map(|arg| {
+                arg.to_str()
+                    .with_context(|| format!("Invalid utf8 in argument: {arg:?}"))
+            })
+            .collect::<Result<Vec<_>>>()?;
+
+        let body = json!({
+            "AttachStderr": true,
+            "AttachStdout": true,
+            "Cmd": argv,
+            "Env": spec.env,
+            "User": spec.user,
+            "WorkingDir": spec.workdir,
+        });
+        let response = self
+            .request("POST", &format!("/containers/{name}/exec"), Some(&body))?
+            .check()?;
+        let id = serde_json::from_slice::<IdResponse>(&response.body)
+            .with_context(|| "Failed to parse the exec id")?
+            .id;
+
+        let body = json!({"Detach": false, "Tty": false});
+        let reader = self.stream("POST", &format!("/exec/{id}/start"), Some(&body))?;
+        demultiplex(reader, &mut io::stdout(), &mut io::stderr())
+            .with_context(|| "Failed to read the output of the exec command")?;
+
+        let response = self
+            .request("GET", &format!("/exec/{id}/json"), None)?
+            .check()?;
+        let exit_code = serde_json::from_slice::<ExecInspect>(&response.body)
+            .with_context(|| "Failed to parse the exec state")?
+            .exit_code
+            .unwrap_or(-1);
+
+        if exit_code == 0 {
+            Ok(())
+        } else {
+            Err(Error::Command(exit_status(exit_code)).into())
+        }
+    }
+
+    /// Return true if the `image` is available locally
+    ///
+    /// # Errors
+    ///
+    /// If the request failed
+    pub fn has_image(&self, image: &str) -> Result<bool> {
+        let response = self.request("GET", &format!("/images/{image}/json"), None)?;
+        if response.status == 404 {
+            return Ok(false);
+        }
+
+        response.check().map(|_| true)
+    }
+
+    /// Return the [`ContainerState`] of the container `name` if it exists
+    ///
+    /// # Errors
+    ///
+    /// If the request failed or the response could not be parsed
+    pub fn inspect(&self, name: &str) -> Result<Option<ContainerState>> {
+        let response = self.request("GET", &format!("/containers/{name}/json"), None)?;
+        if response.status == 404 {
+            return Ok(None);
+        }
+
+        let inspect = serde_json::from_slice::<Inspect>(&response.check()?.body)
+            .with_context(|| format!("Failed to parse the state of container '{name}'"))?;
+        Ok(Some(ContainerState {
+            labels: inspect.config.labels.unwrap_or_default(),
+            running: inspect.state.running,
+        }))
+    }
+
+    /// Pull the `image` from its registry
+    ///
+    /// # Errors
+    ///
+    /// If the request failed or the engine reported an error while pulling
+    pub fn pull(&self, image: &str) -> Result<()> {
+        info!("Pulling the image '{image}'");
+        let (name, tag) = split_image(image);
+        let mut path = format!("/images/create?fromImage={name}");
+        if let Some(tag) = tag {
+            path.push_str("&tag=");
+            path.push_str(tag);
+        }
+
+        // The progress is streamed as json lines and errors after the start only show up there
+        let reader = BufReader::new(self.stream("POST", &path, None)?);
+        for line in reader.lines() {
+            let line = line.with_context(|| "Failed to read the progress of pulling the image")?;
+            let Ok(progress) = serde_json::from_str::<Value>(&line) else {
+                continue;
+            };
+            if let Some(error) = progress.get("error").and_then(Value::as_str) {
+                return Err(Error::ImageMissing(format!(
+                    "Failed to pull the image '{image}': {error}"
+                ))
+                .into());
+            }
+            if let Some(status) = progress.get("status").and_then(Value::as_str) {
+                debug!("{status}");
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Follow the logs of the container `name` and return on the first line equal to `marker`
+    ///
+    /// # Errors
+    ///
+    /// If the container exited before printing the `marker`
+    pub fn wait_for_line(&self, name: &str, marker: &str) -> Result<()> {
+        let reader = self.stream(
+            "GET",
+            &format!("/containers/{name}/logs?follow=true&stdout=true&stderr=false"),
+            None,
+        )?;
+
+        let mut line = Vec::new();
+        let mut found = false;
+        for_each_frame(reader, |_, payload| {
+            for byte in payload {
+                if *byte == b'\n' {
+                    found = String::from_utf8_lossy(&line).trim() == marker;
+                    line.clear();
+                } else {
+                    line.push(*byte);
+                }
+
+                if found {
+                    return Ok(false);
+                }
+            }
+            Ok(true)
+        })?;
+
+        if found {
+            Ok(())
+        } else {
+            let code = self.wait(name)?;
+            Err(anyhow!(
+                "The container '{name}' exited with exit code {code} before '{marker}'"
+            ))
+        }
+    }
+
+    /// Remove the container `name` and stop it if necessary
+    ///
+    /// A container which doesn't exist is not an error.
+    ///
+    /// # Errors
+    ///
+    /// If the container could not be removed
+    pub fn remove(&self, name: &str) -> Result<()> {
+        let response = self.request("DELETE", &format!("/containers/{name}?force=true"), None)?;
+        if response.status == 404 {
+            Ok(())
+        } else {
+            response.check().map(|_| ())
+        }
+    }
+
+    /// Wait for the container `name` to exit and return its exit code
+    ///
+    /// # Errors
+    ///
+    /// If the request failed
+    pub fn wait(&self, name: &str) -> Result<i64> {
+        let response = self
+            .request("POST", &format!("/containers/{name}/wait"), None)?
+            .check()?;
+        serde_json::from_slice::<WaitResponse>(&response.body)
+            .map(|wait| wait.status_code)
+            .with_context(|| "Failed to parse the response of waiting for the container")
+    }
+
+    /// Send the request and read the whole response
+    ///
+    /// # Errors
+    ///
+    /// If connecting to the socket or the http communication failed
+    pub fn request(&self, method: &str, path: &str, body: Option<&Value>) -> Result<Response> {
+        let (status, mut reader) = self.send(method, path, body)?;
+        let mut body = vec![];
+        reader
+            .read_to_end(&mut body)
+            .with_context(|| "Failed to read the response body")?;
+
+        Ok(Response { body, status })
+    }
+
+    fn send(&self, method: &str, path: &str, body: Option<&Value>) -> Result<(u16, Box<dyn Read>)> {
+        let mut stream = UnixStream::connect(&self.socket).with_context(|| {
+            format!(
+                "Failed to connect to the container engine socket '{}'",
+                self.socket.display()
+            )
+        })?;
+
+        let body = body.map(Value::to_string).unwrap_or_default();
+        let request = format!(
+            "{method} /{API_VERSION}{path} HTTP/1.1\r\nHost: localhost\r\nConnection: \
+             close\r\nContent-Type: application/json\r\nContent-Length: {}\r\n\r\n{body}",
+            body.len()
+        );
+        stream
+            .write_all(request.as_bytes())
+            .with_context(|| "Failed to send the request")?;
+
+        let mut reader = BufReader::new(stream);
+        let mut line = String::new();
+        reader.read_line(&mut line)?;
+        let status = line
+            .split_whitespace()
+            .nth(1)
+            .and_then(|status| status.parse::<u16>().ok())
+            .with_context(|| format!("Invalid http status line: '{}'", line.trim()))?;
+
+        let mut chunked = false;
+        let mut content_length = None;
+        loop {
+            line.clear();
+            reader.read_line(&mut line)?;
+            let header = line.trim();
+            if header.is_empty() {
+                break;
+            }
+
+            if let Some((key, value)) = header.split_once(':') {
+                let value = value.trim();
+                if key.eq_ignore_ascii_case("transfer-encoding") {
+                    chunked = value.eq_ignore_ascii_case("chunked");
+                } else if key.eq_ignore_ascii_case("content-length") {
+                    content_length = value.parse::<u64>().ok();
+                } else {
+                    // ignore
+                }
+            }
+        }
+
+        let reader: Box<dyn Read> = if chunked {
+            Box::new(ChunkedReader::new(reader))
+        } else if let Some(length) = content_length {
+            Box::new(reader.take(length))
+        } else {
+            Box::new(reader)
+        };
+
+        Ok((status, reader))
+    }
+
+    fn stream(&self, method: &str, path: &str, body: Option<&Value>) -> Result<Box<dyn Read>> {
+        let (status, mut reader) = self.send(method, path, body)?;
+        if status >= 300 {
+            let mut body = vec![];
+            reader.read_to_end(&mut body)?;
+            return Err(Response { body, status }.error().into());
+        }
+
+        Ok(reader)
+    }
+}
+
+impl Response {
+    /// Return the response if the request was successful or else the error from the body
+    ///
+    /// # Errors
+    ///
+    /// If the status code is not a success
+    pub fn check(self) -> Result<Self> {
+        if self.status < 300 {
+            Ok(self)
+        } else {
+            Err(self.error().into())
+        }
+    }
+
+    /// The error with the message from the body regardless of the status code
+    fn error(&self) -> Error {
+        let message = serde_json::from_slice::<ErrorMessage>(&self.body).map_or_else(
+            |_| String::from_utf8_lossy(&self.body).trim().to_owned(),
+            |error| error.message,
+        );
+        Error::Api {
+            status: self.status,
+            message,
+        }
+    }
+}
+
+/// Write the frames of a multiplexed stream to `stdout` and `stderr`
+fn demultiplex<R, O, E>(reader: R, stdout: &mut O, stderr: &mut E) -> Result<()>
+where
+    R: Read,
+    O: Write,
+    E: Write,
+{
+    for_each_frame(reader, |stream, payload| {
+        if stream == 2 {
+            stderr.write_all(payload)?;
+        } else {
+            stdout.write_all(payload)?;
+        }
+        Ok(true)
+    })
+}
+
+fn exit_status(code: i32) -> std::process::ExitStatus {
+    use std::os::unix::process::ExitStatusExt;
+
+    std::process::ExitStatus::from_raw((code & 0xff) << 8)
+}
+
+/// Call `f` for each frame of a multiplexed stream until it returns false
+///
+/// Each frame consists of an 8 byte header with the stream type in the first byte and the size
+/// of the payload as big endian u32 in the last four bytes.
+fn for_each_frame<R, F>(mut reader: R, mut f: F) -> Result<()>
+where
+    R: Read,
+    F: FnMut(u8, &[u8]) -> io::Result<bool>,
+{
+    let mut header = [0; 8];
+    let mut payload = vec![];
+    loop {
+        match reader.read_exact(&mut header) {
+            Ok(()) => {}
+            Err(error) if error.kind() == io::ErrorKind::UnexpectedEof => return Ok(()),
+            Err(error) => return Err(error.into()),
+        }
+
+        let size = u32::from_be_bytes([header[4], header[5], header[6], header[7]]);
+        payload.resize(size as usize, 0);
+        reader.read_exact(&mut payload)?;
+
+        if !f(header[0], &payload)? {
+            return Ok(());
+        }
+    }
+}
+
+/// Split the `image` into the name and the tag for pulling it
+///
+/// An image with a digest is pulled by its full reference without a tag. The port of a
+/// registry is not mistaken for a tag.
+fn split_image(image: &str) -> (&str, Option<&str>) {
+    if image.contains('@') {
+        return (image, None);
+    }
+
+    match image.rsplit_once(':') {
+        Some((name, tag)) if !tag.contains('/') => (name, Some(tag)),
+        _ => (image, None),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use core::sync::atomic::{AtomicUsize, Ordering};
+    use std::os::unix::net::UnixListener;
+    use std::thread::JoinHandle;
+
+    use rstest::rstest;
+
+    use super::*;
+
+    /// A fake engine serving one canned response per connection
+    struct FakeServer {
+        handle: JoinHandle<Vec<String>>,
+        socket: PathBuf,
+    }
+
+    impl FakeServer {
+        fn new(responses: Vec<String>) -> Self {
+            static COUNTER: AtomicUsize = AtomicUsize::new(0);
+
+            let socket = std::env::temp_dir().join(format!(
+                "cargo-gungraun-test-{}-{}.sock",
+                std::process::id(),
+                COUNTER.fetch_add(1, Ordering::Relaxed)
+            ));
+            let _ = std::fs::remove_file(&socket);
+            let listener = UnixListener::bind(&socket).unwrap();
+
+            let handle = std::thread::spawn(move || {
+                let mut requests = vec![];
+                for response in responses {
+                    let (stream, _) = listener.accept().unwrap();
+                    let mut reader = BufReader::new(stream);
+
+                    let mut request = String::new();
+                    let mut content_length = 0;
+                    loop {
+                        let mut line = String::new();
+                        reader.read_line(&mut line).unwrap();
+                        if let Some(length) = line.strip_prefix("Content-Length: ") {
+                            content_length = length.trim().parse().unwrap();
+                        }
+                        request.push_str(&line);
+                        if line == "\r\n" {
+                            break;
+                        }
+                    }
+                    let mut body = vec![0; content_length];
+                    reader.read_exact(&mut body).unwrap();
+                    request.push_str(&String::from_utf8(body).unwrap());
+                    requests.push(request);
+
+                    reader.get_mut().write_all(response.as_bytes()).unwrap();
+                }
+                requests
+            });
+
+            Self { handle, socket }
+        }
+
+        fn client(&self) -> Client {
+            Client::new(self.socket.clone())
+        }
+
+        fn requests(self) -> Vec<String> {
+            let requests = self.handle.join().unwrap();
+            std::fs::remove_file(&self.socket).unwrap();
+            requests
+        }
+    }
+
+    fn response(status: &str, body: &str) -> String {
+        format!(
+            "HTTP/1.1 {status}\r\nContent-Type: application/json\r\nContent-Length: \
+             {}\r\n\r\n{body}",
+            body.len()
+        )
+    }
+
+    fn run_spec(pull: bool) -> RunSpec {
+        RunSpec {
+            cmd: vec![],
+            env: vec![],
+            image: "ghcr.io/cargo-gungraun/s390x:0.1.0".to_owned(),
+            labels: vec![],
+            name: "some".to_owned(),
+            privileged: false,
+            pull,
+            seccomp: SeccompProfile::Default,
+            security_opts: vec![],
+            user: "root".to_owned(),
+            userns: None,
+            volumes: vec![],
+        }
+    }
+
+    fn frame(stream: u8, payload: &str) -> Vec<u8> {
+        let mut frame = vec![stream, 0, 0, 0];
+        frame.extend(u32::try_from(payload.len()).unwrap().to_be_bytes());
+        frame.extend(payload.as_bytes());
+        frame
+    }
+
+    #[test]
+    fn chunked_reader() {
+        let data = "4\r\nWiki\r\n7\r\npedia i\r\n0\r\n\r\n";
+        let mut reader = ChunkedReader::new(data.as_bytes());
+        let mut output = String::new();
+        reader.read_to_string(&mut output).unwrap();
+        assert_eq!(output, "Wikipedia i");
+    }
+
+    #[test]
+    fn demultiplex_frames() {
+        let mut data = frame(1, "out\n");
+        data.extend(frame(2, "err\n"));
+        data.extend(frame(1, "more out"));
+
+        let (mut stdout, mut stderr) = (vec![], vec![]);
+        demultiplex(data.as_slice(), &mut stdout, &mut stderr).unwrap();
+
+        assert_eq!(String::from_utf8(stdout).unwrap(), "out\nmore out");
+        assert_eq!(String::from_utf8(stderr).unwrap(), "err\n");
+    }
+
+    #[rstest]
+    #[case::found("200 OK", "{}", true)]
+    #[case::not_found("404 Not Found", r#"{"message": "no such image"}"#, false)]
+    fn has_image(#[case] status: &str, #[case] body: &str, #[case] expected: bool) {
+        let server = FakeServer::new(vec![response(status, body)]);
+
+        assert_eq!(server.client().has_image("some").unwrap(), expected);
+
+        let requests = server.requests();
+        assert!(requests[0].starts_with("GET /v1.41/images/some/json HTTP/1.1\r\n"));
+    }
+
+    #[rstest]
+    #[case::tag("ghcr.io/cargo-gungraun/s390x:0.1.0", ("ghcr.io/cargo-gungraun/s390x", Some("0.1.0")))]
+    #[case::no_tag("ghcr.io/cargo-gungraun/s390x", ("ghcr.io/cargo-gungraun/s390x", None))]
+    #[case::port("localhost:5000/s390x", ("localhost:5000/s390x", None))]
+    #[case::port_and_tag("localhost:5000/s390x:latest", ("localhost:5000/s390x", Some("latest")))]
+    #[case::digest("s390x@sha256:abc", ("s390x@sha256:abc", None))]
+    fn split_image_into_name_and_tag(#[case] image: &str, #[case] expected: (&str, Option<&str>)) {
+        assert_eq!(split_image(image), expected);
+    }
+
+    #[test]
+    fn create_when_image_missing_and_not_pulling() {
+        let server = FakeServer::new(vec![response(
+            "404 Not Found",
+            r#"{"message": "no such image"}"#,
+        )]);
+
+        let error = server.client().create(&run_spec(false)).unwrap_err();
+        assert_eq!(Error::exit_code_of(&error), 12);
+
+        server.requests();
+    }
+
+    #[test]
+    fn create_pulls_missing_image() {
+        let server = FakeServer::new(vec![
+            response("404 Not Found", r#"{"message": "no such image"}"#),
+            response(
+                "200 OK",
+                "{\"status\": \"Pulling\"}\n{\"status\": \"Done\"}\n",
+            ),
+            response("201 Created", r#"{"Id": "id"}"#),
+            response("204 No Content", ""),
+        ]);
+
+        server.client().create(&run_spec(true)).unwrap();
+
+        let requests = server.requests();
+        assert!(requests[1].starts_with(
+            "POST /v1.41/images/create?fromImage=ghcr.io/cargo-gungraun/s390x&tag=0.1.0 HTTP/1.1"
+        ));
+        assert!(requests[2].starts_with("POST /v1.41/containers/create?name=some HTTP/1.1"));
+    }
+
+    #[test]
+    fn pull_when_error_in_progress() {
+        let server = FakeServer::new(vec![response(
+            "200 OK",
+            "{\"status\": \"Pulling\"}\n{\"error\": \"manifest unknown\"}\n",
+        )]);
+
+        let error = server.client().pull("some:1.0").unwrap_err();
+        assert_eq!(
+            error.to_string(),
+            "Failed to pull the image 'some:1.0': manifest unknown"
+        );
+
+        server.requests();
+    }
+
+    #[test]
+    fn inspect_when_not_found() {
+        let server = FakeServer::new(vec![response(
+            "404 Not Found",
+            r#"{"message": "no such container"}"#,
+        )]);
+
+        assert!(server.client().inspect("some").unwrap().is_none());
+
+        let requests = server.requests();
+        assert!(requests[0].starts_with("GET /v1.41/containers/some/json HTTP/1.1\r\n"));
+    }
+
+    #[test]
+    fn inspect_when_running() {
+        let server = FakeServer::new(vec![response(
+            "200 OK",
+            r#"{"Config": {"Labels": {"key": "value"}}, "State": {"Running": true}}"#,
+        )]);
+
+        let state = server.client().inspect("some").unwrap().unwrap();
+        assert!(state.running);
+        assert_eq!(state.labels.get("key").map(String::as_str), Some("value"));
+
+        server.requests();
+    }
+
+    #[test]
+    fn remove_when_error() {
+        let server = FakeServer::new(vec![response(
+            "500 Internal Server Error",
+            r#"{"message": "something went wrong"}"#,
+        )]);
+
+        let error = server.client().remove("some").unwrap_err();
+        assert_eq!(
+            error.to_string(),
+            "The container engine api returned status 500: something went wrong"
+        );
+
+        let requests = server.requests();
+        assert!(requests[0].starts_with("DELETE /v1.41/containers/some?force=true HTTP/1.1"));
+    }
+
+    #[test]
+    fn wait_for_line_when_found() {
+        let mut body = frame(1, "some output\ncargo-gungraun: boot");
+        body.extend(frame(1, "strap finished\nignored\n"));
+        let mut response = format!(
+            "HTTP/1.1 200 OK\r\nTransfer-Encoding: chunked\r\n\r\n{:x}\r\n",
+            body.len()
+        )
+        .into_bytes();
+        response.extend(body);
+        response.extend(b"\r\n0\r\n\r\n");
+
+        let server = FakeServer::new(vec![String::from_utf8(response).unwrap()]);
+        server
+            .client()
+            .wait_for_line("some", "cargo-gungraun: bootstrap finished")
+            .unwrap();
+
+        let requests = server.requests();
+        assert!(requests[0].starts_with(
+            "GET /v1.41/containers/some/logs?follow=true&stdout=true&stderr=false HTTP/1.1"
+        ));
+    }
+
+    #[test]
+    fn wait_for_line_when_exited() {
+        let server = FakeServer::new(vec![
+            "HTTP/1.1 200 OK\r\n\r\n".to_owned(),
+            response("200 OK", r#"{"StatusCode": 3}"#),
+        ]);
+
+        let error = server.client().wait_for_line("some", "marker").unwrap_err();
+        assert_eq!(
+            error.to_string(),
+            "The container 'some' exited with exit code 3 before 'marker'"
+        );
+
+        let requests = server.requests();
+        assert!(requests[1].starts_with("POST /v1.41/containers/some/wait HTTP/1.1"));
+    }
+}
@@ -0,0 +1,216 @@
+//! The `clean` command to remove leftovers of previous runs
+
+use std::process::Stdio;
+
+use anyhow::{Context, Result};
+use camino::Utf8Path;
+use log::{debug, info};
+
+use super::{labels, pid_namespace, Backend, Command, CommandExt, Engine, Options};
+use crate::meta::{HostData, CARGO_GUNGRAUN_VERSION, CONTAINER_NAME_PREFIX, IMAGE_REPOSITORY};
+
+/// Stop stale containers and remove the files generated by cargo-gungraun
+///
+/// A container is stale if the cargo-gungraun process which created it is not running anymore.
+/// Containers created in another pid namespace or on another host are never stale, since their
+/// process can't be checked.
+/// The generated files are the `seccomp.json`, all log files and the env and summary files of
+/// containers which are not in use anymore in the gungraun home directory. If `images` is
+/// true, also remove all cargo-gungraun images with a version other than
+/// [`CARGO_GUNGRAUN_VERSION`].
+///
+/// # Errors
+///
+/// If the container engine could not be executed or removing any of the files failed
+pub fn clean(images: bool, options: &Options) -> Result<()> {
+    let engine = Engine::from_env()?;
+    let backend = Backend::from_env(engine)?;
+    let host = HostData::new(
+        options.offline,
+        options.manifest_path.as_deref(),
+        options.toolchain.as_deref(),
+    )?;
+
+    let (stale, in_use) = containers(&backend)?;
+    for name in stale {
+        info!("Stopping stale container '{name}'");
+        backend.remove(&name)?;
+    }
+
+    remove_generated_files(&host.gungraun_home, &in_use)?;
+
+    if images {
+        let output = Command::new(engine)?
+            .args(["images", "--format", "{{.Repository}}:{{.Tag}}"])
+            .run_with_output()?;
+
+        for image in output.lines().filter(|i| is_outdated_image(i)) {
+            info!("Removing outdated image '{image}'");
+            Command::new(engine)?
+                .args(["rmi", image])
+                .stdout(Stdio::null())
+                .run()
+                .with_context(|| format!("Failed to remove the image '{image}'"))?;
+        }
+    }
+
+    Ok(())
+}
+
+/// Return the names of the stale containers and of the containers which are still in use
+///
+/// A container is in use if it's kept, the process which created it is still running or this
+/// process can't be checked.
+fn containers(backend: &Backend) -> Result<(Vec<String>, Vec<String>)> {
+    // Not all engines support filtering by name with a regex, so the names are filtered here
+    let output = Command::new(backend.engine())?
+        .args(["ps", "--all", "--format", "{{.Names}}"])
+        .run_with_output()?;
+
+    let namespace = pid_namespace();
+    let mut stale_names = vec![];
+    let mut in_use = vec![];
+    for name in output
+        .lines()
+        .map(str::trim)
+        .filter(|n| n.starts_with(CONTAINER_NAME_PREFIX))
+    {
+        let Some(state) = backend.inspect(name)? else {
+            continue;
+        };
+
+        if state.labels.contains_key(labels::KEEP) {
+            info!("Skipping kept container '{name}'. Use `cargo gungraun down` to stop it");
+            in_use.push(name.to_owned());
+            continue;
+        }
+
+        // Containers without a pid label were created by an older version of cargo-gungraun
+        let owner = state
+            .labels
+            .get(labels::PID)
+            .and_then(|pid| pid.parse::<u32>().ok());
+        if owner.is_some() && state.labels.get(labels::PID_NAMESPACE) != Some(&namespace) {
+            info!(
+                "Skipping container '{name}': The owning process runs in another pid namespace or \
+                 on another host"
+            );
+            in_use.push(name.to_owned());
+        } else if owner.map_or(true, |pid| !is_process_alive(pid)) {
+            stale_names.push(name.to_owned());
+        } else {
+            debug!("Skipping container '{name}': The owning process is still running");
+            in_use.push(name.to_owned());
+        }
+    }
+
+    Ok((stale_names, in_use))
+}
+
+/// Return true if the process `pid` is running
+///
+/// `kill -0` also fails for processes of other users, so `/proc` is preferred if it exists.
+fn is_process_alive(pid: u32) -> bool {
+    let proc = Utf8Path::new("/proc");
+    if proc.is_dir() {
+        return proc.join(pid.to_string()).exists();
+    }
+
+    std::process::Command::new("kill")
+        .args(["-0", &pid.to_string()])
+        .stdout(Stdio::null())
+        .stderr(Stdio::null())
+        .status()
+        .is_ok_and(|status| status.success())
+}
+
+/// Return true if the file at `path` was generated and can be removed
+///
+/// The env file `<container>.env` and the summary files `<container>.<pid>.summary` of a
+/// container which is still `in_use` are kept.
+fn is_generated_file(path: &Utf8Path, in_use: &[String]) -> bool {
+    match path.extension() {
+        Some("log") => true,
+        Some("env" | "summary") => path
+            .file_stem()
+            .and_then(|stem| stem.split('.').next())
+            .is_some_and(|name| !in_use.iter().any(|n| n == name)),
+        _ => path.file_name() == Some("seccomp.json"),
+    }
+}
+
+fn is_outdated_image(image: &str) -> bool {
+    image
+        .strip_prefix(IMAGE_REPOSITORY)
+        .and_then(|rest| rest.strip_prefix('/'))
+        .and_then(|rest| rest.rsplit_once(':'))
+        .is_some_and(|(_, tag)| tag != CARGO_GUNGRAUN_VERSION && tag != "<none>")
+}
+
+fn remove_generated_files(gungraun_home: &Utf8Path, in_use: &[String]) -> Result<()> {
+    let entries = gungraun_home
+        .read_dir_utf8()
+        .with_context(|| format!("Failed to read the directory '{gungraun_home}'"))?;
+
+    for entry in entries {
+        let entry = entry?;
+        if entry.file_type()?.is_file() && is_generated_file(entry.path(), in_use) {
+            debug!("Removing '{}'", entry.path());
+            std::fs::remove_file(entry.path())
+                .with_context(|| format!("Failed to remove '{}'", entry.path()))?;
+        }
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use rstest::rstest;
+
+    use super::*;
+
+    #[rstest]
+    #[case::seccomp("/gungraun_home/seccomp.json", true)]
+    #[case::log("/gungraun_home/qemu.log", true)]
+    #[case::env_not_in_use("/gungraun_home/cargo-gungraun-0123456789abcdef.env", true)]
+    #[case::env_in_use("/gungraun_home/cargo-gungraun-fedcba9876543210.env", false)]
+    #[case::summary_not_in_use("/gungraun_home/cargo-gungraun-0123456789abcdef.1234.summary", true)]
+    #[case::summary_in_use("/gungraun_home/cargo-gungraun-fedcba9876543210.1234.summary", false)]
+    #[case::other_json("/gungraun_home/other.json", false)]
+    #[case::no_extension("/gungraun_home/seccomp", false)]
+    fn is_generated_file_when(#[case] path: &str, #[case] expected: bool) {
+        let in_use = ["cargo-gungraun-fedcba9876543210".to_owned()];
+        assert_eq!(is_generated_file(Utf8Path::new(path), &in_use), expected);
+    }
+
+    #[test]
+    fn is_process_alive_when_running() {
+        assert!(is_process_alive(std::process::id()));
+    }
+
+    #[test]
+    fn is_process_alive_when_other_user() {
+        // The init process usually belongs to root, so `kill -0` fails for other users
+        assert!(is_process_alive(1));
+    }
+
+    #[test]
+    fn is_process_alive_when_not_running() {
+        // Larger than the maximum pid of linux
+        assert!(!is_process_alive(u32::MAX));
+    }
+
+    #[rstest]
+    #[case::older("ghcr.io/cargo-gungraun/s390x-unknown-linux-gnu:0.0.1", true)]
+    #[case::current(
+        &format!("ghcr.io/cargo-gungraun/s390x-unknown-linux-gnu:{CARGO_GUNGRAUN_VERSION}"),
+        false
+    )]
+    #[case::none_tag("ghcr.io/cargo-gungraun/s390x-unknown-linux-gnu:<none>", false)]
+    #[case::other_repository("docker.io/library/debian:0.0.1", false)]
+    #[case::similar_repository("ghcr.io/cargo-gungraun-other/some:0.0.1", false)]
+    fn is_outdated_image_when(#[case] image: &str, #[case] expected: bool) {
+        assert_eq!(is_outdated_image(image), expected);
+    }
+}
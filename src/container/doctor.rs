@@ -0,0 +1,201 @@
+//! The `doctor` command to check the setup of cargo-gungraun
+
+use std::process::Stdio;
+
+use anyhow::{anyhow, Result};
+use camino::Utf8Path;
+use colored::Colorize;
+
+use super::{is_target_installed, Backend, Command, CommandExt, Engine, Options};
+use crate::error::Error;
+use crate::meta::{self, HostData};
+use crate::{compat, envs, Target};
+
+/// Check the container engine, the workspace and, with a `target`, the image and the standard
+/// library of the target
+///
+/// Each check is printed with the hints of a failure. Missing items which are downloaded on
+/// the first run are only failures with [`Options::offline`].
+///
+/// # Errors
+///
+/// If any of the checks failed
+pub fn doctor(target: Option<Target>, options: &Options) -> Result<()> {
+    let mut failed = 0;
+    let mut report = |name: &str, result: Result<String>| match result {
+        Ok(detail) => println!("{} {name}: {detail}", "ok".green().bold()),
+        Err(error) => {
+            failed += 1;
+            println!("{} {name}: {error:#}", "failed".red().bold());
+            // The hint to run the doctor is pointless here
+            for hint in Error::hints_of(&error)
+                .into_iter()
+                .filter(|h| !h.contains("cargo gungraun doctor"))
+            {
+                println!("       {}: {hint}", "hint".magenta().bold());
+            }
+        }
+    };
+
+    let engine = Engine::from_env().map_err(|error| Error::Config(format!("{error:#}")).into());
+    let engine = match engine {
+        Ok(engine) => {
+            report(
+                "Container engine",
+                engine
+                    .resolve()
+                    .map(|path| format!("{} at '{}'", engine.executable(), path.display())),
+            );
+            report("Container engine connection", check_connection(engine));
+            Some(engine)
+        }
+        Err(error) => {
+            report("Container engine", Err(error));
+            None
+        }
+    };
+
+    let mut rust = None;
+    match HostData::new(
+        options.offline,
+        options.manifest_path.as_deref(),
+        options.toolchain.as_deref(),
+    ) {
+        Ok(host) => {
+            report(
+                "Workspace",
+                Ok(format!(
+                    "gungraun {} in '{}'",
+                    host.gungraun_version, host.workspace_root
+                )),
+            );
+            report(
+                "Current directory",
+                if host.current_dir.starts_with(&host.workspace_root) {
+                    Ok(host.current_dir.to_string())
+                } else {
+                    Err(Error::OutsideWorkspace {
+                        current_dir: host.current_dir.to_string(),
+                        workspace_root: host.workspace_root.to_string(),
+                    }
+                    .into())
+                },
+            );
+            report(
+                "Compatibility",
+                compat::check(&host.metadata, host.gungraun_runner.as_deref())
+                    .map(|()| "gungraun and the gungraun-runner are compatible".to_owned()),
+            );
+            report("gungraun-runner", check_runner(&host, options.offline));
+            match host.rust_sysroot() {
+                Ok(sysroot) => {
+                    report(
+                        "Toolchain",
+                        Ok(format!(
+                            "{} in '{sysroot}'",
+                            host.toolchain.as_deref().unwrap_or("custom toolchain")
+                        )),
+                    );
+                    rust = Some((host.toolchain, sysroot));
+                }
+                Err(error) => report("Toolchain", Err(error)),
+            }
+        }
+        Err(error) => report("Workspace", Err(error)),
+    }
+
+    if let Some(target) = target {
+        if let Some((toolchain, sysroot)) = &rust {
+            report(
+                "Standard library",
+                check_target(target, toolchain.as_deref(), sysroot, options.offline),
+            );
+        }
+        if let Some(engine) = engine {
+            report("Image", check_image(engine, target, options.offline));
+        }
+    } else {
+        println!("Use `--target` to also check the image and the standard library of a target");
+    }
+
+    if failed == 0 {
+        Ok(())
+    } else {
+        Err(anyhow!("{failed} of the checks failed"))
+    }
+}
+
+/// Check that the standard library of the `target` is installed in the `sysroot`
+fn check_target(
+    target: Target,
+    toolchain: Option<&str>,
+    sysroot: &Utf8Path,
+    offline: bool,
+) -> Result<String> {
+    if is_target_installed(target, sysroot) {
+        return Ok(format!("installed for '{target}'"));
+    }
+
+    match toolchain {
+        Some(toolchain) if offline => Err(anyhow!(
+            "The standard library of the target '{target}' is not installed. Install it with \
+             `rustup target add --toolchain {toolchain} {target}`"
+        )),
+        Some(_) => Ok(format!(
+            "not installed for '{target}'. It is installed on the first run"
+        )),
+        None => Err(Error::Toolchain(format!(
+            "The standard library of the target '{target}' is not installed in the sysroot \
+             '{sysroot}'"
+        ))
+        .into()),
+    }
+}
+
+/// Check that the container engine is running and reachable
+fn check_connection(engine: Engine) -> Result<String> {
+    Command::new(engine)?
+        .arg("info")
+        .stdout(Stdio::null())
+        .stderr(Stdio::null())
+        .run()
+        .map(|()| "reachable".to_owned())
+        .map_err(|error| {
+            Error::EngineUnreachable(format!(
+                "The container engine {} is not running or not reachable: {error}",
+                engine.executable()
+            ))
+            .into()
+        })
+}
+
+/// Check that the image of the `target` is available locally
+fn check_image(engine: Engine, target: Target, offline: bool) -> Result<String> {
+    let image = meta::image(target);
+    if Backend::from_env(engine)?.has_image(&image)? {
+        Ok(format!("'{image}' is available locally"))
+    } else if offline {
+        Err(Error::ImageMissing(format!("The image '{image}' is not available locally")).into())
+    } else {
+        Ok(format!("'{image}' is pulled on the first run"))
+    }
+}
+
+/// Check that the gungraun-runner is available
+fn check_runner(host: &HostData, offline: bool) -> Result<String> {
+    match host.runner() {
+        Some(path) if path.is_file() => Ok(format!("'{path}'")),
+        Some(path) if host.gungraun_runner.is_some() => Err(anyhow!(
+            "The gungraun-runner '{path}' from {} doesn't exist",
+            envs::GUNGRAUN_RUNNER
+        )),
+        _ if offline => Err(anyhow!(
+            "No gungraun-runner {} found. It can't be downloaded offline",
+            host.gungraun_version
+        )),
+        _ => Ok(format!(
+            "gungraun-runner {} is downloaded or built on the first run",
+            host.gungraun_version
+        )),
+    }
+}
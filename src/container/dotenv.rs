@@ -0,0 +1,361 @@
+//! The dotenv file with extra environment variables for the container and the guest
+//!
+//! Each line of the file is empty, a comment starting with `#` or an assignment
+//! `[export] KEY=VALUE`. The value is either
+//!
+//! * unquoted: Surrounding whitespace is trimmed and a ` #` starts a comment
+//! * single quoted: The value is taken literally and may span multiple lines
+//! * double quoted: The escapes `\n`, `\t`, `\"`, `\\` and `\$` are recognized and the value
+//!   may span multiple lines
+//!
+//! `${VAR}` and `$VAR` in unquoted and double quoted values are expanded with the variables
+//! defined earlier in the file or else the environment of the host. Unset variables expand to
+//! an empty string.
+
+use std::env::VarError;
+
+use anyhow::{anyhow, Context, Result};
+use camino::{Utf8Path, Utf8PathBuf};
+
+use crate::envs;
+
+/// Load the dotenv file given with `--env-file`, [`envs::CARGO_GUNGRAUN_ENV_FILE`] or else the
+/// `default` from the manifest
+///
+/// Without any file, no variables are returned. An empty [`envs::CARGO_GUNGRAUN_ENV_FILE`]
+/// disables the `default`.
+///
+/// # Errors
+///
+/// If the file could not be read or is invalid
+pub fn load(path: Option<&Utf8Path>, default: Option<&Utf8Path>) -> Result<Vec<(String, String)>> {
+    let path = match path {
+        Some(path) => path.to_owned(),
+        None => match std::env::var(envs::CARGO_GUNGRAUN_ENV_FILE) {
+            Ok(value) if value.is_empty() => return Ok(vec![]),
+            Ok(value) => Utf8PathBuf::from(value),
+            Err(VarError::NotUnicode(_)) => {
+                return Err(anyhow!(
+                    "Invalid {}: Not utf8",
+                    envs::CARGO_GUNGRAUN_ENV_FILE
+                ))
+            }
+            Err(VarError::NotPresent) => match default {
+                Some(path) => path.to_owned(),
+                None => return Ok(vec![]),
+            },
+        },
+    };
+
+    let content = std::fs::read_to_string(&path)
+        .with_context(|| format!("Failed to read the env file '{path}'"))?;
+    parse(&content, |key| std::env::var(key).ok())
+        .with_context(|| format!("Invalid env file '{path}'"))
+}
+
+/// Parse the `content` of a dotenv file
+///
+/// Variables which are not defined in the file are looked up with `lookup`. Later definitions
+/// of the same variable replace earlier ones.
+///
+/// # Errors
+///
+/// If a line is not a valid assignment or a quoted value is not terminated
+pub fn parse<F>(content: &str, lookup: F) -> Result<Vec<(String, String)>>
+where
+    F: Fn(&str) -> Option<String>,
+{
+    let mut parser = Parser {
+        chars: content.chars().collect(),
+        line: 1,
+        pos: 0,
+    };
+    let mut vars: Vec<(String, String)> = vec![];
+
+    loop {
+        let assignment = parser.next_assignment(&|name: &str| {
+            vars.iter()
+                .find(|(key, _)| key == name)
+                .map(|(_, value)| value.clone())
+                .or_else(|| lookup(name))
+        })?;
+        let Some((key, value)) = assignment else {
+            break;
+        };
+        vars.retain(|(k, _)| *k != key);
+        vars.push((key, value));
+    }
+
+    Ok(vars)
+}
+
+struct Parser {
+    chars: Vec<char>,
+    line: usize,
+    pos: usize,
+}
+
+impl Parser {
+    fn advance(&mut self) -> Option<char> {
+        let c = self.peek()?;
+        self.pos += 1;
+        if c == '\n' {
+            self.line += 1;
+        }
+        Some(c)
+    }
+
+    fn error(&self, message: &str) -> anyhow::Error {
+        anyhow!("Line {}: {message}", self.line)
+    }
+
+    fn expand(&mut self, value: &mut String, lookup: &dyn Fn(&str) -> Option<String>) -> Result<()> {
+        let name = if self.peek() == Some('{') {
+            self.advance();
+            let mut name = String::new();
+            loop {
+                match self.advance() {
+                    Some('}') => break,
+                    Some(c) if c == '_' || c.is_ascii_alphanumeric() => name.push(c),
+                    Some(_) | None => {
+                        return Err(self.error("Invalid or unterminated variable expansion"))
+                    }
+                }
+            }
+            name
+        } else {
+            let mut name = String::new();
+            while let Some(c) = self
+                .peek()
+                .filter(|c| *c == '_' || c.is_ascii_alphanumeric())
+            {
+                name.push(c);
+                self.advance();
+            }
+            if name.is_empty() {
+                value.push('$');
+                return Ok(());
+            }
+            name
+        };
+
+        if let Some(expanded) = lookup(&name) {
+            value.push_str(&expanded);
+        }
+        Ok(())
+    }
+
+    fn next_assignment(
+        &mut self,
+        lookup: &dyn Fn(&str) -> Option<String>,
+    ) -> Result<Option<(String, String)>> {
+        loop {
+            self.skip_blanks();
+            match self.peek() {
+                None => return Ok(None),
+                Some('\n') => {
+                    self.advance();
+                }
+                Some('#') => self.skip_line(),
+                Some(_) => break,
+            }
+        }
+
+        let mut key = self.read_key();
+        if key == "export" && self.peek().is_some_and(|c| c == ' ' || c == '\t') {
+            self.skip_blanks();
+            key = self.read_key();
+        }
+        if key.is_empty() || key.starts_with(|c: char| c.is_ascii_digit()) {
+            return Err(self.error("Expected a variable name"));
+        }
+
+        self.skip_blanks();
+        if self.advance() != Some('=') {
+            return Err(self.error(&format!("Expected '=' after '{key}'")));
+        }
+        self.skip_blanks();
+
+        let value = match self.peek() {
+            Some('\'') => self.read_single_quoted()?,
+            Some('"') => self.read_double_quoted(lookup)?,
+            _ => return self.read_unquoted(lookup).map(|value| Some((key, value))),
+        };
+
+        self.skip_blanks();
+        match self.peek() {
+            None | Some('\n') => {}
+            Some('#') => self.skip_line(),
+            Some(_) => return Err(self.error("Unexpected characters after the quoted value")),
+        }
+
+        Ok(Some((key, value)))
+    }
+
+    fn peek(&self) -> Option<char> {
+        self.chars.get(self.pos).copied()
+    }
+
+    fn read_double_quoted(&mut self, lookup: &dyn Fn(&str) -> Option<String>) -> Result<String> {
+        self.advance();
+        let mut value = String::new();
+        loop {
+            match self.advance() {
+                Some('"') => return Ok(value),
+                Some('\\') => match self.advance() {
+                    Some('n') => value.push('\n'),
+                    Some('t') => value.push('\t'),
+                    Some(c @ ('"' | '\\' | '$')) => value.push(c),
+                    Some(c) => {
+                        value.push('\\');
+                        value.push(c);
+                    }
+                    None => break,
+                },
+                Some('$') => self.expand(&mut value, lookup)?,
+                Some(c) => value.push(c),
+                None => break,
+            }
+        }
+
+        Err(self.error("Unterminated double quoted value"))
+    }
+
+    fn read_key(&mut self) -> String {
+        let mut key = String::new();
+        while let Some(c) = self
+            .peek()
+            .filter(|c| *c == '_' || c.is_ascii_alphanumeric())
+        {
+            key.push(c);
+            self.advance();
+        }
+        key
+    }
+
+    fn read_single_quoted(&mut self) -> Result<String> {
+        self.advance();
+        let mut value = String::new();
+        loop {
+            match self.advance() {
+                Some('\'') => return Ok(value),
+                Some(c) => value.push(c),
+                None => return Err(self.error("Unterminated single quoted value")),
+            }
+        }
+    }
+
+    fn read_unquoted(&mut self, lookup: &dyn Fn(&str) -> Option<String>) -> Result<String> {
+        let mut value = String::new();
+        while let Some(c) = self.peek() {
+            match c {
+                '\n' => break,
+                '#' if value.is_empty() || value.ends_with([' ', '\t']) => {
+                    self.skip_line();
+                    break;
+                }
+                '$' => {
+                    self.advance();
+                    self.expand(&mut value, lookup)?;
+                }
+                c => {
+                    value.push(c);
+                    self.advance();
+                }
+            }
+        }
+
+        Ok(value.trim_end().to_owned())
+    }
+
+    fn skip_blanks(&mut self) {
+        while self
+            .peek()
+            .is_some_and(|c| c == ' ' || c == '\t' || c == '\r')
+        {
+            self.advance();
+        }
+    }
+
+    fn skip_line(&mut self) {
+        while self.peek().is_some_and(|c| c != '\n') {
+            self.advance();
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use rstest::rstest;
+
+    use super::*;
+
+    fn lookup(key: &str) -> Option<String> {
+        match key {
+            "HOST_VAR" => Some("host".to_owned()),
+            "HOME" => Some("/home/user".to_owned()),
+            _ => None,
+        }
+    }
+
+    fn pairs(pairs: &[(&str, &str)]) -> Vec<(String, String)> {
+        pairs
+            .iter()
+            .map(|(k, v)| ((*k).to_owned(), (*v).to_owned()))
+            .collect()
+    }
+
+    #[rstest]
+    #[case::empty("", &[])]
+    #[case::simple("FOO=bar", &[("FOO", "bar")])]
+    #[case::empty_value("FOO=", &[("FOO", "")])]
+    #[case::export("export FOO=bar", &[("FOO", "bar")])]
+    #[case::export_as_key("export=bar", &[("export", "bar")])]
+    #[case::spaces(" FOO = bar baz  ", &[("FOO", "bar baz")])]
+    #[case::crlf("FOO=bar\r\nBAR=baz\r\n", &[("FOO", "bar"), ("BAR", "baz")])]
+    #[case::comments("# comment\n\nFOO=bar # trailing\n  # indented", &[("FOO", "bar")])]
+    #[case::hash_in_value("FOO=bar#baz", &[("FOO", "bar#baz")])]
+    #[case::single_quoted("FOO='$HOME # not a comment'", &[("FOO", "$HOME # not a comment")])]
+    #[case::single_quoted_multiline("FOO='a\nb'", &[("FOO", "a\nb")])]
+    #[case::double_quoted(r#"FOO="a \"b\" \\ \$HOME\n""#, &[("FOO", "a \"b\" \\ $HOME\n")])]
+    #[case::double_quoted_comment(r#"FOO="bar" # comment"#, &[("FOO", "bar")])]
+    #[case::expand_braces("FOO=${HOME}/data", &[("FOO", "/home/user/data")])]
+    #[case::expand_plain("FOO=$HOST_VAR-x", &[("FOO", "host-x")])]
+    #[case::expand_double_quoted(r#"FOO="${HOST_VAR} $HOME""#, &[("FOO", "host /home/user")])]
+    #[case::expand_unset("FOO=a${MISSING}b", &[("FOO", "ab")])]
+    #[case::expand_file_var("FOO=one\nBAR=${FOO}-two", &[("FOO", "one"), ("BAR", "one-two")])]
+    #[case::expand_lonely_dollar("FOO=5$", &[("FOO", "5$")])]
+    #[case::file_overrides_host("HOME=/other\nFOO=$HOME", &[("HOME", "/other"), ("FOO", "/other")])]
+    #[case::redefined("FOO=1\nBAR=2\nFOO=3", &[("BAR", "2"), ("FOO", "3")])]
+    fn parse_when_valid(#[case] content: &str, #[case] expected: &[(&str, &str)]) {
+        assert_eq!(parse(content, lookup).unwrap(), pairs(expected));
+    }
+
+    #[rstest]
+    #[case::missing_equals("FOO bar", "Line 1: Expected '=' after 'FOO'")]
+    #[case::missing_key("\n=bar", "Line 2: Expected a variable name")]
+    #[case::leading_digit("1FOO=bar", "Line 1: Expected a variable name")]
+    #[case::unterminated_single("FOO='bar", "Line 1: Unterminated single quoted value")]
+    #[case::unterminated_double("FOO=\"bar\n", "Line 2: Unterminated double quoted value")]
+    #[case::unterminated_expansion(
+        "FOO=${BAR",
+        "Line 1: Invalid or unterminated variable expansion"
+    )]
+    #[case::after_quotes(
+        "FOO='bar' baz",
+        "Line 1: Unexpected characters after the quoted value"
+    )]
+    fn parse_when_invalid(#[case] content: &str, #[case] expected: &str) {
+        assert_eq!(parse(content, lookup).unwrap_err().to_string(), expected);
+    }
+
+    #[test]
+    fn load_when_file_does_not_exist() {
+        assert_eq!(
+            load(Some(Utf8Path::new("/does/not/exist.env")), None)
+                .unwrap_err()
+                .to_string(),
+            "Failed to read the env file '/does/not/exist.env'"
+        );
+    }
+}
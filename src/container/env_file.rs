@@ -0,0 +1,167 @@
+//! The env file which transports environment variables into the qemu guest
+//!
+//! The file is written to the gungraun home directory, which is shared with the guest, and
+//! sourced by the remote shell before the command is executed. All values are quoted, so they
+//! are never interpreted by the shell.
+
+use core::fmt::Write as _;
+use std::fs::{OpenOptions, Permissions};
+use std::io::Write;
+use std::os::unix::fs::{OpenOptionsExt, PermissionsExt};
+
+use anyhow::{anyhow, Context, Result};
+use camino::Utf8Path;
+
+/// Render the `envs` as shell `export` statements
+///
+/// # Errors
+///
+/// If a key is not a valid shell variable name
+pub fn render(envs: &[(String, String)]) -> Result<String> {
+    let mut content = String::new();
+    for (key, value) in envs {
+        if !is_valid_key(key) {
+            return Err(anyhow!(
+                "Unable to forward the environment variable '{key}' into the guest: Invalid \
+                 variable name"
+            ));
+        }
+        writeln!(content, "export {key}={}", quote(value)).unwrap();
+    }
+
+    Ok(content)
+}
+
+/// Quote the `value` for a POSIX shell
+///
+/// The value is enclosed in single quotes and single quotes within the value are replaced with
+/// `'\''`.
+#[must_use]
+pub fn quote(value: &str) -> String {
+    format!("'{}'", value.replace('\'', r"'\''"))
+}
+
+/// Write the `envs` to the env file at `path`
+///
+/// The file is only readable by the current user because it may contain secrets. The
+/// permissions of an existing file are restricted before writing, too.
+///
+/// # Errors
+///
+/// If a key is invalid or writing the file failed
+pub fn write(path: &Utf8Path, envs: &[(String, String)]) -> Result<()> {
+    let content = render(envs)?;
+
+    OpenOptions::new()
+        .write(true)
+        .create(true)
+        .truncate(true)
+        .mode(0o600)
+        .open(path)
+        .and_then(|mut file| {
+            file.set_permissions(Permissions::from_mode(0o600))?;
+            file.write_all(content.as_bytes())
+        })
+        .with_context(|| format!("Failed to write the env file '{path}'"))
+}
+
+fn is_valid_key(key: &str) -> bool {
+    let mut bytes = key.bytes();
+    bytes
+        .next()
+        .is_some_and(|b| b.is_ascii_alphabetic() || b == b'_')
+        && bytes.all(|b| b.is_ascii_alphanumeric() || b == b'_')
+}
+
+#[cfg(test)]
+mod tests {
+    use std::process::Command;
+
+    use camino::Utf8PathBuf;
+    use rstest::rstest;
+
+    use super::*;
+
+    #[rstest]
+    #[case::simple("value", "'value'")]
+    #[case::empty("", "''")]
+    #[case::spaces("with spaces", "'with spaces'")]
+    #[case::single_quote("it's", r"'it'\''s'")]
+    #[case::double_quote(r#"say "hi""#, r#"'say "hi"'"#)]
+    #[case::dollar("$HOME", "'$HOME'")]
+    #[case::backticks("`id`", "'`id`'")]
+    #[case::newline("a\nb", "'a\nb'")]
+    fn quote_value(#[case] value: &str, #[case] expected: &str) {
+        assert_eq!(quote(value), expected);
+    }
+
+    #[rstest]
+    #[case::simple("FOO", true)]
+    #[case::underscore("_FOO_1", true)]
+    #[case::leading_digit("1FOO", false)]
+    #[case::dash("FOO-BAR", false)]
+    #[case::empty("", false)]
+    #[case::injection("FOO=1; id", false)]
+    fn is_valid_key_when(#[case] key: &str, #[case] expected: bool) {
+        assert_eq!(is_valid_key(key), expected);
+    }
+
+    #[test]
+    fn render_when_invalid_key() {
+        assert_eq!(
+            render(&[("FOO BAR".to_owned(), "value".to_owned())])
+                .unwrap_err()
+                .to_string(),
+            "Unable to forward the environment variable 'FOO BAR' into the guest: Invalid variable \
+             name"
+        );
+    }
+
+    #[test]
+    fn write_when_file_exists() {
+        let path = Utf8PathBuf::try_from(std::env::temp_dir())
+            .unwrap()
+            .join(format!("cargo-gungraun-test-{}.env", std::process::id()));
+        std::fs::write(&path, "").unwrap();
+        std::fs::set_permissions(&path, Permissions::from_mode(0o644)).unwrap();
+
+        let result = write(&path, &[("FOO".to_owned(), "secret".to_owned())]);
+        let mode = std::fs::metadata(&path).unwrap().permissions().mode();
+        let content = std::fs::read_to_string(&path).unwrap();
+        std::fs::remove_file(&path).unwrap();
+
+        result.unwrap();
+        assert_eq!(mode & 0o777, 0o600);
+        assert_eq!(content, "export FOO='secret'\n");
+    }
+
+    #[test]
+    fn render_is_sourced_verbatim() {
+        let values = [
+            "with spaces",
+            "it's",
+            r#"double "quotes""#,
+            "$HOME and ${PATH}",
+            "`id` $(id)",
+            "back\\slash",
+            "new\nline",
+            "; exit 1",
+        ];
+        let envs = values
+            .iter()
+            .enumerate()
+            .map(|(index, value)| (format!("VAR_{index}"), (*value).to_owned()))
+            .collect::<Vec<_>>();
+
+        let mut script = render(&envs).unwrap();
+        for index in 0..values.len() {
+            writeln!(script, "printf '%s\\0' \"$VAR_{index}\"").unwrap();
+        }
+
+        let output = Command::new("sh").args(["-c", &script]).output().unwrap();
+        assert!(output.status.success());
+
+        let actual = String::from_utf8(output.stdout).unwrap();
+        assert_eq!(actual.split_terminator('\0').collect::<Vec<_>>(), values);
+    }
+}
@@ -0,0 +1,295 @@
+//! The selection of environment variables forwarded into the container and the guest
+//!
+//! A selection is a comma separated list (quoted like csv) of rules:
+//!
+//! * `KEY=VALUE`: Set `KEY` explicitly to `VALUE`
+//! * `PATTERN`: Forward all variables of the host whose name matches the glob `PATTERN`
+//! * `!PATTERN`: Don't forward variables matching `PATTERN` even if included by a pattern
+//!
+//! Explicit `KEY=VALUE` pairs are never excluded. The variables of the dotenv file are applied
+//! first, so the rules override them.
+
+use std::borrow::Cow;
+use std::collections::BTreeMap;
+use std::env::VarError;
+use std::ffi::OsStr;
+
+use anyhow::{anyhow, Context, Result};
+use simplematch::DoWild;
+
+use crate::envs;
+use crate::meta::{parse_csv, SIMPLEMATCH_OPTIONS};
+
+/// The value shown instead of the value of secret-like variables
+const MASK: &str = "****";
+
+/// The patterns of variable names whose values are masked in the logs
+///
+/// The patterns are matched case-insensitive.
+const SECRET_PATTERNS: [&str; 7] = [
+    "*TOKEN*",
+    "*SECRET*",
+    "*PASSWORD*",
+    "*PASSWD*",
+    "*CREDENTIAL*",
+    "*_KEY",
+    "*PRIVATE*",
+];
+
+/// A rule of an environment variable selection
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Rule {
+    /// Exclude all variables matching the pattern
+    Exclude(String),
+    /// Include all variables of the host matching the pattern
+    Include(String),
+    /// Set the variable explicitly
+    Set(String, String),
+}
+
+/// The selected environment variables for the container and the qemu guest
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct EnvSelection {
+    /// The variables set in the container
+    pub container: Vec<(String, String)>,
+    /// The variables set in the qemu guest
+    pub guest: Vec<(String, String)>,
+}
+
+impl EnvSelection {
+    /// Select the variables from the environment of this process
+    ///
+    /// The rules of [`envs::CARGO_GUNGRAUN_ENVS`] apply to the container and the guest,
+    /// [`envs::CARGO_GUNGRAUN_CONTAINER_ENVS`] only to the container and
+    /// [`envs::CARGO_GUNGRAUN_GUEST_ENVS`] only to the guest. The `dotenv` variables are set
+    /// in both before any rule is applied.
+    ///
+    /// # Errors
+    ///
+    /// If any of the environment variables is invalid
+    pub fn from_env(dotenv: &[(String, String)]) -> Result<Self> {
+        let common = dotenv
+            .iter()
+            .map(|(key, value)| Rule::Set(key.clone(), value.clone()))
+            .chain(rules_from_env(envs::CARGO_GUNGRAUN_ENVS)?)
+            .collect::<Vec<_>>();
+        let container = rules_from_env(envs::CARGO_GUNGRAUN_CONTAINER_ENVS)?;
+        let guest = rules_from_env(envs::CARGO_GUNGRAUN_GUEST_ENVS)?;
+
+        let vars = std::env::vars_os()
+            .filter_map(|(key, value)| Some((key.into_string().ok()?, value.into_string().ok()?)))
+            .collect::<Vec<_>>();
+
+        Ok(Self {
+            container: select(&[common.as_slice(), &container].concat(), &vars),
+            guest: select(&[common.as_slice(), &guest].concat(), &vars),
+        })
+    }
+}
+
+/// Return true if the variable `key` looks like it contains a secret
+///
+/// Additional patterns can be given with [`envs::CARGO_GUNGRAUN_SECRET_ENVS`].
+#[must_use]
+pub fn is_secret(key: &str) -> bool {
+    let key = key.to_uppercase();
+    let is_match = |pattern: &str| {
+        pattern
+            .to_uppercase()
+            .as_str()
+            .dowild_with(key.as_str(), SIMPLEMATCH_OPTIONS)
+    };
+
+    SECRET_PATTERNS.iter().any(|pattern| is_match(pattern))
+        || std::env::var(envs::CARGO_GUNGRAUN_SECRET_ENVS).is_ok_and(|value| {
+            value
+                .split(',')
+                .map(str::trim)
+                .filter(|p| !p.is_empty())
+                .any(is_match)
+        })
+}
+
+/// Mask the value of an `arg` in the form `KEY=VALUE` if the key looks like a secret
+#[must_use]
+pub fn mask(arg: &str) -> Cow<'_, str> {
+    match arg.split_once('=') {
+        Some((key, _)) if is_secret(key) => Cow::Owned(format!("{key}={MASK}")),
+        _ => Cow::Borrowed(arg),
+    }
+}
+
+/// Render the `command` for the logs with the values of secret-like variables masked
+#[must_use]
+pub fn masked_command(command: &std::process::Command) -> String {
+    core::iter::once(command.get_program())
+        .chain(command.get_args())
+        .map(OsStr::to_string_lossy)
+        .map(|arg| format!("{:?}", mask(&arg)))
+        .collect::<Vec<_>>()
+        .join(" ")
+}
+
+/// Parse the `rules` of a selection
+///
+/// # Errors
+///
+/// If the rules are not valid csv or a rule is empty
+pub fn parse_rules(rules: &str) -> Result<Vec<Rule>> {
+    let mut parsed = vec![];
+    for rule in parse_csv(rules, b',')? {
+        let rule = rule.trim();
+        if let Some(pattern) = rule.strip_prefix('!') {
+            if pattern.is_empty() {
+                return Err(anyhow!("Empty exclude pattern"));
+            }
+            parsed.push(Rule::Exclude(pattern.to_owned()));
+        } else if let Some((key, value)) = rule.split_once('=') {
+            if key.is_empty() {
+                return Err(anyhow!("Missing the variable name in '{rule}'"));
+            }
+            parsed.push(Rule::Set(key.to_owned(), value.to_owned()));
+        } else if !rule.is_empty() {
+            parsed.push(Rule::Include(rule.to_owned()));
+        } else {
+            // skip empty rules
+        }
+    }
+
+    Ok(parsed)
+}
+
+/// Apply the `rules` to the variables `vars` and return the selected variables sorted by key
+///
+/// Later rules override the values of earlier rules.
+#[must_use]
+pub fn select(rules: &[Rule], vars: &[(String, String)]) -> Vec<(String, String)> {
+    let is_excluded = |key: &str| {
+        rules.iter().any(|rule| {
+            matches!(rule, Rule::Exclude(pattern)
+                if pattern.as_str().dowild_with(key, SIMPLEMATCH_OPTIONS))
+        })
+    };
+
+    let mut selected = BTreeMap::new();
+    for rule in rules {
+        match rule {
+            Rule::Exclude(_) => {}
+            Rule::Include(pattern) => {
+                for (key, value) in vars {
+                    if pattern
+                        .as_str()
+                        .dowild_with(key.as_str(), SIMPLEMATCH_OPTIONS)
+                        && !is_excluded(key)
+                    {
+                        selected.insert(key.clone(), value.clone());
+                    }
+                }
+            }
+            Rule::Set(key, value) => {
+                selected.insert(key.clone(), value.clone());
+            }
+        }
+    }
+
+    selected.into_iter().collect()
+}
+
+fn rules_from_env(key: &str) -> Result<Vec<Rule>> {
+    match std::env::var(key) {
+        Ok(value) => parse_rules(&value).with_context(|| format!("Invalid {key}")),
+        Err(VarError::NotUnicode(_)) => Err(anyhow!("Invalid {key}: Not utf8")),
+        Err(VarError::NotPresent) => Ok(vec![]),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use rstest::rstest;
+
+    use super::*;
+
+    fn vars() -> Vec<(String, String)> {
+        [
+            ("MY_FOO", "foo"),
+            ("MY_BAR", "bar"),
+            ("MY_SECRET", "secret"),
+            ("OTHER", "other"),
+        ]
+        .map(|(k, v)| (k.to_owned(), v.to_owned()))
+        .to_vec()
+    }
+
+    fn pairs(pairs: &[(&str, &str)]) -> Vec<(String, String)> {
+        pairs
+            .iter()
+            .map(|(k, v)| ((*k).to_owned(), (*v).to_owned()))
+            .collect()
+    }
+
+    #[rstest]
+    #[case::empty("", vec![])]
+    #[case::set("FOO=bar", vec![Rule::Set("FOO".to_owned(), "bar".to_owned())])]
+    #[case::set_empty("FOO=", vec![Rule::Set("FOO".to_owned(), String::new())])]
+    #[case::set_with_equals("FOO=a=b", vec![Rule::Set("FOO".to_owned(), "a=b".to_owned())])]
+    #[case::include("MY_*", vec![Rule::Include("MY_*".to_owned())])]
+    #[case::exclude("!MY_*", vec![Rule::Exclude("MY_*".to_owned())])]
+    #[case::quoted(
+        "\"FOO=with,comma\",BAR",
+        vec![Rule::Set("FOO".to_owned(), "with,comma".to_owned()), Rule::Include("BAR".to_owned())]
+    )]
+    fn parse_rules_when_valid(#[case] rules: &str, #[case] expected: Vec<Rule>) {
+        assert_eq!(parse_rules(rules).unwrap(), expected);
+    }
+
+    #[rstest]
+    #[case::empty_exclude("!", "Empty exclude pattern")]
+    #[case::missing_key("=value", "Missing the variable name in '=value'")]
+    fn parse_rules_when_invalid(#[case] rules: &str, #[case] expected: &str) {
+        assert_eq!(parse_rules(rules).unwrap_err().to_string(), expected);
+    }
+
+    #[rstest]
+    #[case::none("", &[])]
+    #[case::exact("OTHER", &[("OTHER", "other")])]
+    #[case::glob("MY_*", &[("MY_BAR", "bar"), ("MY_FOO", "foo"), ("MY_SECRET", "secret")])]
+    #[case::exclude("MY_*,!MY_SECRET", &[("MY_BAR", "bar"), ("MY_FOO", "foo")])]
+    #[case::exclude_first("!MY_S*,MY_*", &[("MY_BAR", "bar"), ("MY_FOO", "foo")])]
+    #[case::set_not_excluded("MY_SECRET=explicit,!MY_*", &[("MY_SECRET", "explicit")])]
+    #[case::set_overrides("MY_FOO,MY_FOO=new", &[("MY_FOO", "new")])]
+    #[case::not_found("MISSING", &[])]
+    fn select_rules(#[case] rules: &str, #[case] expected: &[(&str, &str)]) {
+        let rules = parse_rules(rules).unwrap();
+        assert_eq!(select(&rules, &vars()), pairs(expected));
+    }
+
+    #[rstest]
+    #[case::token("GITHUB_TOKEN", true)]
+    #[case::lowercase("my_password", true)]
+    #[case::api_key("API_KEY", true)]
+    #[case::secret("AWS_SECRET_ACCESS_KEY", true)]
+    #[case::plain("RUST_LOG", false)]
+    #[case::keyboard("KEYBOARD", false)]
+    fn is_secret_when(#[case] key: &str, #[case] expected: bool) {
+        assert_eq!(is_secret(key), expected);
+    }
+
+    #[rstest]
+    #[case::secret("GITHUB_TOKEN=abc", "GITHUB_TOKEN=****")]
+    #[case::plain("RUST_LOG=debug", "RUST_LOG=debug")]
+    #[case::no_pair("--env", "--env")]
+    fn mask_arg(#[case] arg: &str, #[case] expected: &str) {
+        assert_eq!(mask(arg), expected);
+    }
+
+    #[test]
+    fn masked_command_hides_secrets() {
+        let mut command = std::process::Command::new("podman");
+        command.args(["run", "--env", "API_TOKEN=abc", "--env", "FOO=bar"]);
+
+        assert_eq!(
+            masked_command(&command),
+            r#""podman" "run" "--env" "API_TOKEN=****" "--env" "FOO=bar""#
+        );
+    }
+}
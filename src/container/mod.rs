@@ -2,42 +2,213 @@
 
 // spell-checker: ignore idirafter nocapture termmodes
 
+pub mod api;
+mod clean;
+mod doctor;
+pub mod dotenv;
+pub mod env_file;
+pub mod env_selection;
+pub mod runner;
+pub mod seccomp;
+pub mod volume;
+
+/// Names of the labels attached to every container created by cargo-gungraun
+pub mod labels {
+    /// A hash over the settings the container was created with
+    pub const CONFIG: &str = "cargo-gungraun.config";
+    /// Present if the container is kept running after cargo-gungraun exits
+    pub const KEEP: &str = "cargo-gungraun.keep";
+    /// The process id of the cargo-gungraun process which created the container
+    pub const PID: &str = "cargo-gungraun.pid";
+    /// The host and pid namespace of the process in [`PID`]
+    pub const PID_NAMESPACE: &str = "cargo-gungraun.pid-namespace";
+    /// The rust target triple the container was created for
+    pub const TARGET: &str = "cargo-gungraun.target";
+    /// The version of cargo-gungraun which created the container
+    pub const VERSION: &str = "cargo-gungraun.version";
+    /// The workspace root on the host
+    pub const WORKSPACE: &str = "cargo-gungraun.workspace";
+}
+
 use core::fmt::Write as _;
 use core::ops::{Deref, DerefMut};
-use core::time::Duration;
+use std::collections::HashMap;
 use std::ffi::{OsStr, OsString};
-use std::fs::File;
-use std::io::{self, BufRead, BufReader, IsTerminal, Write};
+use std::io::{self, BufRead, BufReader, IsTerminal};
 use std::os::unix::ffi::OsStrExt;
 use std::path::PathBuf;
 use std::process::Stdio;
-use std::thread::sleep;
 
 use anyhow::{anyhow, Context, Result};
-use log::{debug, log_enabled};
+use camino::{Utf8Path, Utf8PathBuf};
+use log::{debug, info, log_enabled, warn};
 use which::which;
 
-use crate::error::Error;
-use crate::meta::{ContainerData, EngineData, HostData};
-use crate::{envs, Target};
+pub use self::clean::clean;
+pub use self::doctor::doctor;
+use self::runner::RunnerSource;
+use self::seccomp::SeccompProfile;
+use self::volume::{Mount, MountOptions};
+use crate::error::{Error, REGRESSION_EXIT_CODE};
+use crate::meta::{
+    ContainerData, EngineData, Harness, HarnessPolicy, HostData, Selection, CARGO_GUNGRAUN_VERSION,
+    CONTAINER_NAME_PREFIX,
+};
+use crate::{compat, envs, Target};
+
+/// The line printed by the bootstrap script when the container is ready
+const BOOTSTRAP_FINISHED: &str = "cargo-gungraun: bootstrap finished";
+
+/// How cargo-gungraun talks to the container engine
+///
+/// The backend is selected with [`envs::CARGO_GUNGRAUN_BACKEND`].
+#[derive(Debug, Clone)]
+pub enum Backend {
+    /// The REST API of the engine served over a unix socket
+    ///
+    /// Interactive commands which need a tty still use the cli of the `engine`.
+    Api {
+        /// The api client
+        client: api::Client,
+        /// The engine serving the api
+        engine: Engine,
+    },
+    /// The command-line interface of the engine
+    Cli(Engine),
+}
 
-#[derive(Debug, Clone, Copy)]
+/// The supported container engines
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub enum Engine {
-    Podman,
+    /// Docker
     Docker,
+    /// Nerdctl for containerd
+    Nerdctl,
+    /// Podman
+    Podman,
+    /// Podman with `--remote` connecting to a podman service
+    PodmanRemote,
 }
 
 impl Engine {
-    /// TODO: DOCS
+    /// Select the container engine from [`envs::CARGO_GUNGRAUN_ENGINE`]
+    ///
+    /// If the environment variable is not set, the engine is detected from the file name of
+    /// [`envs::CARGO_GUNGRAUN_ENGINE_PATH`]. Otherwise, the first executable found of podman,
+    /// docker and nerdctl in this order is used.
     ///
     /// # Errors
-    pub fn resolve(&self) -> Result<PathBuf> {
-        let name = match self {
-            Self::Podman => "podman",
+    ///
+    /// If the environment variable contains an invalid engine
+    pub fn from_env() -> Result<Self> {
+        if let Some(var) = std::env::var_os(envs::CARGO_GUNGRAUN_ENGINE) {
+            return Self::try_from(var.as_os_str());
+        }
+
+        if let Some(path) = std::env::var_os(envs::CARGO_GUNGRAUN_ENGINE_PATH) {
+            let path = PathBuf::from(path);
+            return path
+                .file_name()
+                .with_context(|| format!("Invalid {}", envs::CARGO_GUNGRAUN_ENGINE_PATH))
+                .and_then(Self::try_from)
+                .with_context(|| {
+                    format!(
+                        "Unable to detect the container engine from {} '{}'. Please set {}",
+                        envs::CARGO_GUNGRAUN_ENGINE_PATH,
+                        path.display(),
+                        envs::CARGO_GUNGRAUN_ENGINE
+                    )
+                });
+        }
+
+        Ok([Self::Podman, Self::Docker, Self::Nerdctl]
+            .into_iter()
+            .find(|engine| engine.resolve().is_ok())
+            .unwrap_or(Self::Docker))
+    }
+
+    /// Return true if the engine is podman or podman remote
+    #[must_use]
+    pub const fn is_podman(self) -> bool {
+        matches!(self, Self::Podman | Self::PodmanRemote)
+    }
+
+    /// Return true if the container engine runs rootless
+    ///
+    /// # Errors
+    ///
+    /// If the container engine could not be executed
+    pub fn is_rootless(self) -> Result<bool> {
+        let mut command = Command::new(self)?;
+        if self.is_podman() {
+            command.args(["info", "--format", "{{.Host.Security.Rootless}}"]);
+            Ok(command.run_with_output()?.trim() == "true")
+        } else {
+            command.args(["info", "--format", "{{json .SecurityOptions}}"]);
+            Ok(command.run_with_output()?.contains("rootless"))
+        }
+    }
+
+    /// The options to mount an executable file read-only
+    #[must_use]
+    pub const fn readonly_exec_options(self) -> &'static str {
+        // Only podman knows the `exec` option. Docker and nerdctl don't mount with `noexec`.
+        if self.is_podman() {
+            "exec,ro"
+        } else {
+            "ro"
+        }
+    }
+
+    /// The name of the executable of the container engine
+    #[must_use]
+    pub const fn executable(self) -> &'static str {
+        match self {
+            Self::Podman | Self::PodmanRemote => "podman",
             Self::Docker => "docker",
-        };
+            Self::Nerdctl => "nerdctl",
+        }
+    }
+
+    /// Return the path to the executable of the container engine
+    ///
+    /// The path can be set explicitly with [`envs::CARGO_GUNGRAUN_ENGINE_PATH`]. Otherwise,
+    /// the executable is searched in the `PATH`.
+    ///
+    /// # Errors
+    ///
+    /// If the executable could not be found
+    pub fn resolve(&self) -> Result<PathBuf> {
+        if let Some(path) = std::env::var_os(envs::CARGO_GUNGRAUN_ENGINE_PATH) {
+            return which(&path).map_err(|error| {
+                Error::EngineMissing(format!(
+                    "Container engine executable from {} not found: '{}': {error}",
+                    envs::CARGO_GUNGRAUN_ENGINE_PATH,
+                    path.to_string_lossy()
+                ))
+                .into()
+            });
+        }
 
-        which(name).with_context(|| "Container engine executable not found")
+        which(self.executable()).map_err(|error| {
+            Error::EngineMissing(format!(
+                "Container engine executable '{}' not found: {error}",
+                self.executable()
+            ))
+            .into()
+        })
+    }
+
+    /// The arguments of the `stop` command for the container with the `name`
+    ///
+    /// A container which doesn't exist is not an error.
+    #[must_use]
+    pub fn stop_args(self, name: &str) -> Vec<&str> {
+        if self.is_podman() {
+            vec!["stop", "--ignore", name]
+        } else {
+            vec!["stop", name]
+        }
     }
 }
 
@@ -47,9 +218,12 @@ impl TryFrom<&OsStr> for Engine {
     fn try_from(value: &OsStr) -> core::result::Result<Self, Self::Error> {
         match value.as_bytes() {
             b"podman" => Ok(Self::Podman),
+            b"podman-remote" => Ok(Self::PodmanRemote),
             b"docker" => Ok(Self::Docker),
+            b"nerdctl" => Ok(Self::Nerdctl),
             _ => Err(anyhow!(
-                "Invalid container engine: '{}'. Expected one of 'podman' or 'docker'",
+                "Invalid container engine: '{}'. Expected one of 'podman', 'podman-remote', \
+                 'docker' or 'nerdctl'",
                 value.to_string_lossy()
             )),
         }
@@ -59,6 +233,357 @@ impl TryFrom<&OsStr> for Engine {
 #[derive(Debug)]
 pub struct Command(std::process::Command);
 
+/// The state of an existing container
+#[derive(Debug)]
+pub struct ContainerState {
+    /// The labels of the container
+    pub labels: HashMap<String, String>,
+    /// True if the container is running
+    pub running: bool,
+}
+
+/// The specification of a command executed in a running container
+#[derive(Debug, Clone)]
+pub struct ExecSpec {
+    /// The command and its arguments
+    pub argv: Vec<OsString>,
+    /// Additional environment variables in the form `KEY=VALUE`
+    pub env: Vec<String>,
+    /// If true, keep the stdin open and allocate a tty if running in a terminal
+    pub interactive: bool,
+    /// The user executing the command
+    pub user: String,
+    /// The working directory of the command
+    pub workdir: String,
+}
+
+/// How a bench was run as recorded by the runner in the summary file
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SummaryRecord {
+    /// The name of the bench
+    pub bench: String,
+    /// The exit code of the bench
+    pub code: i32,
+    /// The harness of the bench like `gungraun` or `criterion`
+    pub harness: String,
+    /// Where the bench ran: `gungraun`, `qemu-user`, `vm`, `skip` or `error`
+    pub run: String,
+}
+
+/// The options of a [`Session`] given on the command line
+#[derive(Debug, Clone, Default)]
+pub struct Options {
+    /// The dotenv file with extra environment variables
+    pub env_file: Option<Utf8PathBuf>,
+    /// If true, keep the container running for reuse by later invocations
+    pub keep: bool,
+    /// The manifest of the package to run `cargo bench` for instead of the current directory
+    pub manifest_path: Option<Utf8PathBuf>,
+    /// What to do with benches which don't use gungraun. Overrides
+    /// [`envs::CARGO_GUNGRAUN_NON_GUNGRAUN`] and the manifest
+    pub non_gungraun: Option<HarnessPolicy>,
+    /// If true, don't access the network and fail early if anything required is missing
+    pub offline: bool,
+    /// The packages and benches selected on the command line
+    pub selection: Selection,
+    /// The toolchain from `+toolchain` which overrides the toolchain selected by rustup
+    pub toolchain: Option<String>,
+}
+
+/// The specification of a container to create and start
+#[derive(Debug, Clone)]
+pub struct RunSpec {
+    /// The command of the container
+    pub cmd: Vec<String>,
+    /// The environment variables in the form `KEY=VALUE`
+    pub env: Vec<String>,
+    /// The image
+    pub image: String,
+    /// The labels attached to the container
+    pub labels: Vec<(String, String)>,
+    /// The name of the container
+    pub name: String,
+    /// If true, run the container with extended privileges
+    pub privileged: bool,
+    /// If false, never pull the image
+    pub pull: bool,
+    /// The seccomp profile
+    pub seccomp: SeccompProfile,
+    /// Additional security options like `label=disable`
+    pub security_opts: Vec<String>,
+    /// The user of the container process
+    pub user: String,
+    /// The user namespace mode
+    pub userns: Option<String>,
+    /// The volumes in the form `HOST:CONTAINER[:OPTIONS]`
+    pub volumes: Vec<String>,
+}
+
+/// A running container with the prepared environment for a target
+///
+/// The container is either started by [`Session::start`] or it's the kept container of the
+/// workspace and target which is reused.
+pub struct Session {
+    backend: Backend,
+    container: ContainerData,
+    engine_data: EngineData,
+    host: HostData,
+    keep: bool,
+    offline: bool,
+    /// What to do with benches which don't use gungraun
+    policy: HarnessPolicy,
+    runner_build: Option<Utf8PathBuf>,
+    target: Target,
+}
+
+/// Extension methods for [`std::process::Command`]
+pub trait CommandExt {
+    /// Execute the command and wait for it to finish
+    ///
+    /// # Errors
+    ///
+    /// If the command could not be spawned or didn't exit successfully
+    fn run(&mut self) -> Result<(), Error>;
+
+    /// Execute the command and return its stdout
+    ///
+    /// The stderr is inherited from the parent process.
+    ///
+    /// # Errors
+    ///
+    /// If the command could not be spawned, didn't exit successfully or the output was not
+    /// valid utf8
+    fn run_with_output(&mut self) -> Result<String>;
+}
+
+impl Backend {
+    /// Select the backend for the `engine` from [`envs::CARGO_GUNGRAUN_BACKEND`]
+    ///
+    /// # Errors
+    ///
+    /// If the environment variable contains an invalid backend or the engine doesn't support
+    /// the api backend
+    pub fn from_env(engine: Engine) -> Result<Self> {
+        match std::env::var(envs::CARGO_GUNGRAUN_BACKEND).as_deref() {
+            Err(_) | Ok("cli") => Ok(Self::Cli(engine)),
+            Ok("api") => Ok(Self::Api {
+                client: api::Client::from_env(engine)?,
+                engine,
+            }),
+            Ok(value) => Err(anyhow!(
+                "Invalid {}: '{value}'. Expected one of 'cli' or 'api'",
+                envs::CARGO_GUNGRAUN_BACKEND
+            )),
+        }
+    }
+
+    /// Create and start the container of the [`RunSpec`]
+    ///
+    /// Returns after the bootstrap script of the container finished.
+    ///
+    /// # Errors
+    ///
+    /// If the container could not be started or the bootstrap script failed
+    pub fn create(&self, spec: &RunSpec) -> Result<()> {
+        match self {
+            Self::Api { client, .. } => {
+                client
+                    .create(spec)
+                    .with_context(|| "Failed to start the container")?;
+                let result = client.wait_for_line(&spec.name, BOOTSTRAP_FINISHED);
+                if result.is_err() {
+                    // Containers created over the api are not removed automatically. Failing to
+                    // remove it must not hide the failed bootstrap
+                    if let Err(error) = client.remove(&spec.name) {
+                        debug!("Failed to remove the container '{}': {error:#}", spec.name);
+                    }
+                }
+                result.context(Error::Bootstrap(spec.name.clone()))
+            }
+            Self::Cli(engine) => {
+                let mut up_command = Command::new(*engine)?;
+                up_command.args(["run", "--detach", "--rm", "--name", &spec.name]);
+                if !spec.pull {
+                    up_command.arg("--pull=never");
+                }
+                for (key, value) in &spec.labels {
+                    up_command.args(["--label", &format!("{key}={value}")]);
+                }
+                up_command.args(spec.cli_args());
+                up_command.args(&spec.cmd);
+
+                debug!(
+                    "Running up cmd: {}",
+                    env_selection::masked_command(&up_command)
+                );
+                up_command
+                    .stdout(Stdio::null())
+                    .run()
+                    .with_context(|| "Failed to start the container")?;
+
+                wait_for_logs(*engine, &spec.name)
+            }
+        }
+    }
+
+    /// Return true if the `image` is available locally
+    ///
+    /// # Errors
+    ///
+    /// If the container engine could not be reached
+    pub fn has_image(&self, image: &str) -> Result<bool> {
+        match self {
+            Self::Api { client, .. } => client.has_image(image),
+            Self::Cli(engine) => Command::new(*engine)?
+                .args(["image", "inspect", image])
+                .stdout(Stdio::null())
+                .stderr(Stdio::null())
+                .status()
+                .map(|status| status.success())
+                .map_err(|error| Error::CommandSpawn(error).into()),
+        }
+    }
+
+    /// The container engine
+    #[must_use]
+    pub const fn engine(&self) -> Engine {
+        match self {
+            Self::Api { engine, .. } | Self::Cli(engine) => *engine,
+        }
+    }
+
+    /// Run the command of the [`ExecSpec`] in the container `name`
+    ///
+    /// # Errors
+    ///
+    /// If the command could not be executed or didn't exit successfully
+    pub fn exec(&self, name: &str, spec: &ExecSpec) -> Result<()> {
+        match self {
+            Self::Api { client, .. } if !spec.interactive => client.exec(name, spec),
+            Self::Api { engine, .. } | Self::Cli(engine) => {
+                let mut exec_command = Command::new(*engine)?;
+                exec_command.args(["exec", "--user", &spec.user, "--workdir", &spec.workdir]);
+                for env in &spec.env {
+                    exec_command.args(["--env", env]);
+                }
+
+                if spec.interactive {
+                    exec_command.arg("--interactive");
+                    if io::stdin().is_terminal() && io::stdout().is_terminal() {
+                        exec_command.arg("--tty");
+                    } else {
+                        debug!("Not running in a terminal. Starting the command without a tty");
+                    }
+                } else if io::stdin().is_terminal()
+                    && io::stdout().is_terminal()
+                    && io::stderr().is_terminal()
+                {
+                    exec_command.arg("-t");
+                } else {
+                    // do nothing
+                }
+
+                exec_command.arg(name);
+                exec_command.args(&spec.argv);
+
+                debug!(
+                    "Running the exec command: {}",
+                    env_selection::masked_command(&exec_command)
+                );
+                exec_command.run().map_err(Into::into)
+            }
+        }
+    }
+
+    /// Return the [`ContainerState`] of the container with the given `name` if it exists
+    ///
+    /// # Errors
+    ///
+    /// If the container engine could not be reached or the output could not be parsed
+    pub fn inspect(&self, name: &str) -> Result<Option<ContainerState>> {
+        let engine = match self {
+            Self::Api { client, .. } => return client.inspect(name),
+            Self::Cli(engine) => *engine,
+        };
+
+        let output = Command::new(engine)?
+            .args([
+                "inspect",
+                "--type",
+                "container",
+                "--format",
+                "{{json .State.Running}} {{json .Config.Labels}}",
+                name,
+            ])
+            .stderr(Stdio::null())
+            .output()
+            .map_err(Error::CommandSpawn)?;
+
+        if !output.status.success() {
+            return Ok(None);
+        }
+
+        let output = String::from_utf8_lossy(&output.stdout);
+        let (running, labels) = output
+            .trim()
+            .split_once(' ')
+            .with_context(|| format!("Invalid output of inspecting container '{name}'"))?;
+
+        Ok(Some(ContainerState {
+            labels: serde_json::from_str::<Option<HashMap<String, String>>>(labels)
+                .with_context(|| format!("Failed to parse the labels of container '{name}'"))?
+                .unwrap_or_default(),
+            running: serde_json::from_str(running)
+                .with_context(|| format!("Failed to parse the state of container '{name}'"))?,
+        }))
+    }
+
+    /// Stop and remove the container with the given `name`
+    ///
+    /// # Errors
+    ///
+    /// If the container could not be removed
+    pub fn remove(&self, name: &str) -> Result<()> {
+        match self {
+            Self::Api { client, .. } => client.remove(name),
+            Self::Cli(engine) => Command::new(*engine)?
+                .args(["rm", "--force", name])
+                .stdout(Stdio::null())
+                .run()
+                .map_err(Into::into),
+        }
+        .with_context(|| format!("Failed to remove the container '{name}'"))
+    }
+
+    /// Stop the container with the given `name`
+    ///
+    /// A container which doesn't exist is not an error. Containers started with the cli are
+    /// removed automatically when stopped.
+    ///
+    /// # Errors
+    ///
+    /// If stopping the container failed
+    pub fn stop(&self, name: &str) -> Result<()> {
+        let engine = match self {
+            Self::Api { .. } => return self.remove(name),
+            Self::Cli(engine) => *engine,
+        };
+
+        // TODO: Instead of Stdio::null route to a log file
+        let result = Command::new(engine)?
+            .args(engine.stop_args(name))
+            .stdout(Stdio::null())
+            .stderr(Stdio::null())
+            .run();
+
+        // Only podman can ignore containers which are already gone
+        match result {
+            Err(_) if !engine.is_podman() && self.inspect(name)?.is_none() => Ok(()),
+            result => result.map_err(Into::into),
+        }
+    }
+}
+
 impl Deref for Command {
     type Target = std::process::Command;
 
@@ -73,308 +598,1237 @@ impl DerefMut for Command {
     }
 }
 
+impl CommandExt for std::process::Command {
+    fn run(&mut self) -> Result<(), Error> {
+        self.status()
+            .map_err(Error::CommandSpawn)
+            .and_then(|status| {
+                if status.success() {
+                    Ok(())
+                } else {
+                    Err(Error::Command(status))
+                }
+            })
+    }
+
+    fn run_with_output(&mut self) -> Result<String> {
+        let output = self
+            .stderr(Stdio::inherit())
+            .output()
+            .map_err(Error::CommandSpawn)
+            .and_then(|output| {
+                if output.status.success() {
+                    Ok(output)
+                } else {
+                    Err(Error::Command(output.status))
+                }
+            })?;
+
+        String::from_utf8(output.stdout)
+            .with_context(|| format!("The output of {self:?} should be valid utf8"))
+    }
+}
+
 impl Command {
     /// TODO: DOCS
     ///
     /// # Errors
     pub fn new(engine: Engine) -> Result<Self> {
         let exe = engine.resolve()?;
-        let cmd = std::process::Command::new(exe);
+        let mut cmd = std::process::Command::new(exe);
+        if engine == Engine::PodmanRemote {
+            cmd.arg("--remote");
+        }
         Ok(Self(cmd))
     }
 }
 
-/// TODO: DOCS
+impl RunSpec {
+    /// The arguments of the cli `run` command which define the environment of the container
+    ///
+    /// The name, labels and command are not included. The arguments end with the image.
+    #[must_use]
+    pub fn cli_args(&self) -> Vec<String> {
+        let mut args = vec![format!("--user={}", self.user)];
+        if let Some(userns) = &self.userns {
+            args.push(format!("--userns={userns}"));
+        }
+
+        match &self.seccomp {
+            SeccompProfile::Default => {}
+            SeccompProfile::File(path) => args.push(format!("--security-opt=seccomp={path}")),
+            SeccompProfile::Unconfined => args.push("--security-opt=seccomp=unconfined".to_owned()),
+        }
+        for opt in &self.security_opts {
+            args.push(format!("--security-opt={opt}"));
+        }
+
+        for volume in &self.volumes {
+            args.push("--volume".to_owned());
+            args.push(volume.clone());
+        }
+
+        for env in &self.env {
+            args.push("--env".to_owned());
+            args.push(env.clone());
+        }
+
+        if self.privileged {
+            args.push("--privileged".to_owned());
+        }
+
+        args.push(self.image.clone());
+        args
+    }
+}
+
+impl Session {
+    /// Start a new container or reuse the kept container of the workspace and target
+    ///
+    /// A kept container is only reused if it was created with the same settings and is
+    /// running. Otherwise, it is replaced with a new kept container if [`Options::keep`]
+    /// is true, or left alone in favor of a new container which is stopped by
+    /// [`Session::finish`]. If [`Options::keep`] is true, the container is not stopped by
+    /// [`Session::finish`].
+    ///
+    /// # Errors
+    ///
+    /// If preparing the environment or starting the container failed
+    pub fn start(target: Target, options: &Options) -> Result<Self> {
+        let host = HostData::new(
+            options.offline,
+            options.manifest_path.as_deref(),
+            options.toolchain.as_deref(),
+        )?;
+        compat::check(&host.metadata, host.gungraun_runner.as_deref())?;
+        host.metadata
+            .check_selection(&options.selection)
+            .map_err(|error| Error::Args(error.to_string()))?;
+        let policy = match options.non_gungraun {
+            Some(policy) => policy,
+            None => HarnessPolicy::from_env()
+                .map_err(|error| Error::Config(format!("{error:#}")))?
+                .or(host.config.non_gungraun)
+                .unwrap_or_default(),
+        };
+        let engine_data = EngineData::new(target, &host, options.env_file.as_deref())?;
+        let container = ContainerData::new(&host, &engine_data)?;
+        let backend = Backend::from_env(engine_data.engine)?;
+
+        let seccomp = engine_data.seccomp.resolve(&engine_data.seccomp_path)?;
+        debug!("Using the seccomp profile: {seccomp:?}");
+
+        let mut session = Self {
+            backend,
+            container,
+            engine_data,
+            host,
+            keep: options.keep,
+            offline: options.offline,
+            policy,
+            runner_build: None,
+            target,
+        };
+
+        if session.offline {
+            session.check_offline()?;
+        } else {
+            session.install_target()?;
+            if runner::ensure(&session.host)?.is_none() {
+                if let RunnerSource::Local { manifest, .. } = &session.host.runner_source {
+                    session.runner_build = Some(runner::source_workspace(manifest)?);
+                }
+            }
+        }
+
+        let mut spec = session.run_spec(seccomp)?;
+        // The profile may change without changing its path
+        let mut settings = spec.cli_args().join("\0");
+        if let SeccompProfile::File(path) = &spec.seccomp {
+            settings.push_str(
+                &std::fs::read_to_string(path)
+                    .with_context(|| format!("Failed to read the seccomp profile '{path}'"))?,
+            );
+        }
+        let config = hex::encode(fnv1a(settings.as_bytes()).to_be_bytes());
+
+        let kept_name = kept_container_name(&session.host.workspace_root, target);
+        match session.backend.inspect(&kept_name)? {
+            Some(state) if state.running && state.labels.get(labels::CONFIG) == Some(&config) => {
+                info!("Reusing the kept container '{kept_name}'");
+                session.container.name = kept_name;
+                session.keep = true;
+                session.write_env_file()?;
+                return Ok(session);
+            }
+            Some(_) if session.keep => {
+                warn!("The kept container '{kept_name}' is stale. Replacing it");
+                session.backend.remove(&kept_name)?;
+            }
+            Some(_) => {
+                warn!(
+                    "The kept container '{kept_name}' is stale. Not using it. Use `cargo gungraun \
+                     up` to replace it"
+                );
+            }
+            None => {}
+        }
+
+        if session.keep {
+            session.container.name = kept_name;
+        }
+
+        spec.name.clone_from(&session.container.name);
+        spec.labels.push((labels::CONFIG.to_owned(), config));
+        if session.keep {
+            spec.labels
+                .push((labels::KEEP.to_owned(), "yes".to_owned()));
+        }
+
+        session.write_env_file()?;
+        session.backend.create(&spec)?;
+        if session.runner_build.is_some() {
+            session.build_runner()?;
+        }
+        Ok(session)
+    }
+
+    /// Check that everything required is available without network access
+    ///
+    /// These are the image, the standard library of the target and the gungraun-runner.
+    ///
+    /// # Errors
+    ///
+    /// If anything is missing. The error lists all missing items
+    pub fn check_offline(&self) -> Result<()> {
+        let mut missing = vec![];
+
+        let image = &self.engine_data.image;
+        let has_image = self.backend.has_image(image)?;
+        if !has_image {
+            missing.push(format!(
+                "The image '{image}' is not available locally. Pull it with `{} pull {image}`",
+                self.engine_data.engine.executable()
+            ));
+        }
+
+        let sysroot = self.host.rust_sysroot()?;
+        if !is_target_installed(self.target, &sysroot) {
+            missing.push(match &self.host.toolchain {
+                Some(toolchain) => format!(
+                    "The standard library of the target '{0}' is not installed for the toolchain \
+                     '{1}'. Install it with `rustup target add --toolchain {1} {0}`",
+                    self.target, toolchain
+                ),
+                None => format!(
+                    "The standard library of the target '{}' is not installed in the sysroot \
+                     '{sysroot}'",
+                    self.target
+                ),
+            });
+        }
+
+        if !self.host.runner().is_some_and(|path| path.is_file()) {
+            missing.push(format!(
+                "No gungraun-runner {0} found. Set {1} to the path of a local gungraun-runner or \
+                 copy it to '{2}'",
+                self.host.gungraun_version,
+                envs::GUNGRAUN_RUNNER,
+                self.host.cached_runner()
+            ));
+        }
+
+        for extra in &self.host.extra_runners {
+            let path = self.host.gungraun_home.join(extra.cache_path());
+            if !path.is_file() {
+                missing.push(format!(
+                    "No gungraun-runner {} found for the benches {}. Copy it to '{path}'",
+                    extra.version,
+                    extra.benches.join(", ")
+                ));
+            }
+        }
+
+        if missing.is_empty() {
+            Ok(())
+        } else {
+            let mut message = "Running offline is not possible:".to_owned();
+            for item in missing {
+                message.push_str("\n  - ");
+                message.push_str(&item);
+            }
+            if has_image {
+                Err(anyhow!(message))
+            } else {
+                Err(Error::ImageMissing(message).into())
+            }
+        }
+    }
+
+    /// Boot the qemu guest in the container if it is not already running
+    ///
+    /// # Errors
+    ///
+    /// If booting the guest failed
+    pub fn boot_vm(&self) -> Result<()> {
+        let mut argv = self.qemu_runner_argv();
+
+        if log_enabled!(log::Level::Trace) {
+            argv.extend(["--debug", "trace"].map(OsString::from));
+        } else if log_enabled!(log::Level::Debug) {
+            argv.extend(["--debug", "debug"].map(OsString::from));
+        } else {
+            // do nothing
+        }
+
+        for (env, arg) in [
+            (envs::CARGO_GUNGRAUN_QEMU_TIMEOUT, "--timeout"),
+            (envs::CARGO_GUNGRAUN_QEMU_EXTRA_ARGS, "--extra-args"),
+        ] {
+            if let Some(value) = std::env::var_os(env) {
+                argv.extend([OsString::from(arg), value]);
+            }
+        }
+        if let Some(accel) = &self.engine_data.accelerator {
+            argv.extend([OsString::from("--accel"), OsString::from(accel)]);
+        }
+        argv.extend(["--no-run", "--"].map(OsString::from));
+
+        self.exec(&argv, false).context(Error::VmBoot)
+    }
+
+    /// Build the gungraun-runner from the mounted gungraun source in the container
+    ///
+    /// The runner is built for the container, stored in the cache in the gungraun home and
+    /// installed in the container.
+    ///
+    /// # Errors
+    ///
+    /// If building or installing the runner failed
+    pub fn build_runner(&self) -> Result<()> {
+        let container = &self.container;
+        let cached = container
+            .gungraun_home
+            .join(self.host.runner_source.cache_path());
+        let target_dir = container.gungraun_home.join("runner/build");
+        info!("Building the gungraun-runner from source. This may take a while");
+
+        let script = format!(
+            "cargo build --release --package gungraun-runner --manifest-path {0}/Cargo.toml \
+             --target-dir {1} && mkdir -p {2} && cp {1}/release/gungraun-runner {3}.tmp && mv \
+             {3}.tmp {3}",
+            env_file::quote(container.gungraun_source.as_str()),
+            env_file::quote(target_dir.as_str()),
+            env_file::quote(cached.parent().unwrap_or(&cached).as_str()),
+            env_file::quote(cached.as_str()),
+        );
+        let mut spec = self.exec_spec(
+            ["/bin/sh", "-c", &script].map(OsString::from).to_vec(),
+            false,
+        );
+        spec.workdir = container.gungraun_source.to_string();
+        self.backend
+            .exec(&container.name, &spec)
+            .with_context(|| "Failed to build the gungraun-runner")?;
+
+        spec.argv = [
+            "install",
+            "-m",
+            "755",
+            cached.as_str(),
+            container.gungraun_runner.as_str(),
+        ]
+        .map(OsString::from)
+        .to_vec();
+        "root".clone_into(&mut spec.user);
+        self.backend
+            .exec(&container.name, &spec)
+            .with_context(|| "Failed to install the gungraun-runner in the container")
+    }
+
+    /// Run the command `argv` in the prepared environment of the container
+    ///
+    /// The working directory is the current directory within the workspace. If `interactive`
+    /// is true, the stdin is kept open and a tty is allocated if running in a terminal.
+    ///
+    /// # Errors
+    ///
+    /// If the command could not be executed or didn't exit successfully
+    pub fn exec(&self, argv: &[OsString], interactive: bool) -> Result<()> {
+        let spec = self.exec_spec(argv.to_vec(), interactive);
+        self.backend.exec(&self.container.name, &spec)
+    }
+
+    /// Run `cargo bench` with the `cargo_args` in the container
+    ///
+    /// The benches which don't use gungraun are skipped or run according to the
+    /// [`HarnessPolicy`]. With [`HarnessPolicy::Error`], this fails before running anything if
+    /// the `selection` contains such benches. A summary of what ran where is printed at the
+    /// end.
+    ///
+    /// # Errors
+    ///
+    /// If the benchmarks could not be run or failed
+    pub fn exec_bench(&self, cargo_args: Vec<OsString>, selection: &Selection) -> Result<()> {
+        let others = self
+            .host
+            .metadata
+            .selected_benches(selection)
+            .into_iter()
+            .filter(|bench| matches!(bench.harness, Harness::Criterion | Harness::Libtest))
+            .map(|bench| format!("{} ({})", bench.target.name, bench.harness))
+            .collect::<Vec<_>>();
+        if !others.is_empty() {
+            if self.policy == HarnessPolicy::Error {
+                return Err(Error::Config(format!(
+                    "The benches {} don't use gungraun. Select the benches with `--bench` or use \
+                     `--non-gungraun=<POLICY>` to skip or run them",
+                    others.join(", ")
+                ))
+                .into());
+            }
+            debug!(
+                "Applying the policy '{}' to the benches {}",
+                self.policy,
+                others.join(", ")
+            );
+        }
+
+        let benches = self
+            .host
+            .metadata
+            .benches()
+            .iter()
+            .map(|bench| format!("{}={}", bench.target.name.replace('-', "_"), bench.harness))
+            .collect::<Vec<_>>();
+        let summary_file_name = self.summary_file_name();
+        let summary = self.host.gungraun_home.join(&summary_file_name);
+        std::fs::write(&summary, "")
+            .with_context(|| format!("Failed to create the summary file '{summary}'"))?;
+
+        let mut argv = vec![OsString::from("cargo"), OsString::from("bench")];
+        argv.extend(cargo_args);
+
+        let mut spec = self.exec_spec(argv, false);
+        spec.env.extend([
+            format!("{}={}", envs::CARGO_GUNGRAUN_BENCHES, benches.join(" ")),
+            format!("{}={}", envs::CARGO_GUNGRAUN_NON_GUNGRAUN, self.policy),
+            format!(
+                "{}={}",
+                envs::CARGO_GUNGRAUN_SUMMARY_FILE,
+                self.container.gungraun_home.join(&summary_file_name)
+            ),
+        ]);
+        let result = self.backend.exec(&self.container.name, &spec);
+
+        let records = match std::fs::read_to_string(&summary) {
+            Ok(content) => parse_summary(&content),
+            Err(error) => {
+                warn!("Failed to read the summary file '{summary}': {error}");
+                vec![]
+            }
+        };
+        if let Err(error) = std::fs::remove_file(&summary) {
+            debug!("Failed to remove the summary file '{summary}': {error}");
+        }
+        if let Some(message) = summary_message(&records) {
+            info!("{message}");
+        }
+
+        result.map_err(|error| bench_error(error, &records))
+    }
+
+    /// Open an interactive shell in the container or, if `guest` is true, in the qemu guest
+    ///
+    /// # Errors
+    ///
+    /// If the shell could not be started or booting the guest failed
+    pub fn exec_shell(&self, guest: bool) -> Result<()> {
+        if guest {
+            self.boot_vm()?;
+
+            // Without a command, the qemu runner opens a login shell in the guest
+            let mut argv = self.qemu_runner_argv();
+            argv.push(OsString::from("--"));
+            self.exec(&argv, true)
+        } else {
+            self.exec(&[OsString::from(&self.container.shell)], true)
+        }
+    }
+
+    /// Stop the container unless it should be kept
+    ///
+    /// # Errors
+    ///
+    /// If stopping the container failed
+    pub fn finish(self) -> Result<()> {
+        if self.keep {
+            info!(
+                "Keeping the container '{}' running. Use `cargo gungraun down` to stop it",
+                self.container.name
+            );
+            return Ok(());
+        }
+
+        debug!("Stopping the container '{}' ...", &self.container.name);
+        self.backend.stop(&self.container.name)
+    }
+
+    fn exec_spec(&self, argv: Vec<OsString>, interactive: bool) -> ExecSpec {
+        let container = &self.container;
+        let engine_data = &self.engine_data;
+
+        // TODO: The log-file doesn't have any effect, and neither the others below
+        let mut executor_args = format!(
+            "--qemu-arch {} --log-file {}/qemu.log",
+            self.target, container.gungraun_home
+        );
+
+        // TODO: add other envs,
+        // TODO: QEMU_EXTRA_ARGS, QEMU_MAX_MEM, QEMU_MIN_MEM, QEMU_MEM, same with cpus
+        // TODO: QEMU_LOG_FILE
+
+        if log_enabled!(log::Level::Trace) {
+            write!(executor_args, " --debug trace").unwrap();
+        } else if log_enabled!(log::Level::Debug) {
+            write!(executor_args, " --debug debug").unwrap();
+        } else {
+            // do nothing
+        }
+
+        let env_file = container.gungraun_home.join(self.env_file_name());
+        write!(executor_args, " --env-file {env_file}").unwrap();
+
+        let mut env = vec![
+            format!("{}={}", envs::GUNGRAUN_EXECUTOR, container.qemu_runner),
+            format!("{}={}", envs::GUNGRAUN_EXECUTOR_ARGS, executor_args),
+            // TODO: CLEANUP, doesn't work. Error message is
+            // dbclient: Failed reading termmodes
+            //
+            // dbclient: Connection to root@localhost:10022 exited: Failed to set raw TTY mode
+            // "GUNGRAUN_NOCAPTURE=yes",
+            "GUNGRAUN_LOG=warn".to_owned(),
+            format!("{}={env_file}", envs::CARGO_GUNGRAUN_GUEST_ENV_FILE),
+        ];
+
+        if let Some(accel) = &engine_data.accelerator {
+            env.push(format!("{}={accel}", envs::CARGO_GUNGRAUN_QEMU_ACCELERATOR));
+        }
+
+        for key in [
+            envs::CARGO_GUNGRAUN_QEMU_TIMEOUT,
+            envs::CARGO_GUNGRAUN_QEMU_EXTRA_ARGS,
+        ] {
+            if let Ok(value) = std::env::var(key) {
+                env.push(format!("{key}={value}"));
+            }
+        }
+
+        ExecSpec {
+            argv,
+            env,
+            interactive,
+            user: container.user.clone(),
+            workdir: container.current_dir.to_string(),
+        }
+    }
+
+    /// The file name of the env file in the gungraun home directory
+    fn env_file_name(&self) -> String {
+        format!("{}.env", self.container.name)
+    }
+
+    /// The file name of the summary file of this process in the gungraun home directory
+    ///
+    /// The pid keeps concurrent invocations sharing a kept container apart.
+    fn summary_file_name(&self) -> String {
+        format!("{}.{}.summary", self.container.name, std::process::id())
+    }
+
+    /// Install the standard library of the target with rustup if it's missing in the sysroot
+    ///
+    /// Checking the sysroot first avoids the slow and possibly online `rustup target add`.
+    fn install_target(&self) -> Result<()> {
+        let sysroot = self.host.rust_sysroot()?;
+        if is_target_installed(self.target, &sysroot) {
+            debug!(
+                "The standard library of the target '{}' is installed in '{sysroot}'",
+                self.target
+            );
+            return Ok(());
+        }
+
+        match &self.host.toolchain {
+            Some(toolchain) => std::process::Command::new("rustup")
+                .args(["target", "add", "--toolchain", toolchain])
+                .arg(self.target.to_string())
+                .run()
+                .with_context(|| {
+                    format!(
+                        "Failed to install the standard library of the target '{}'",
+                        self.target
+                    )
+                }),
+            None => Err(Error::Toolchain(format!(
+                "The standard library of the target '{}' is not installed in the sysroot \
+                 '{sysroot}'",
+                self.target
+            ))
+            .into()),
+        }
+    }
+
+    fn qemu_runner_argv(&self) -> Vec<OsString> {
+        vec![
+            OsString::from(&self.container.qemu_runner),
+            OsString::from("--qemu-arch"),
+            OsString::from(self.target.to_string()),
+        ]
+    }
+
+    /// The [`RunSpec`] which defines the environment of the container
+    ///
+    /// The name of the container and the labels depending on the reuse of a kept container
+    /// are added by [`Session::start`].
+    #[allow(clippy::too_many_lines)]
+    fn run_spec(&self, seccomp: SeccompProfile) -> Result<RunSpec> {
+        let Self {
+            container,
+            engine_data,
+            host,
+            target,
+            ..
+        } = self;
+
+        let target_upper_env = target.to_upper_env();
+        let gnu_triple = target.to_gnu_triple();
+        let sysroot = format!("/usr/{gnu_triple}");
+
+        let mut volumes = vec![];
+        for (mount, host_path, container_path) in [
+            (
+                Mount::GungraunHome,
+                &host.gungraun_home,
+                &container.gungraun_home,
+            ),
+            (Mount::Target, &host.target_dir, &container.target_dir),
+            (
+                Mount::Workspace,
+                &host.workspace_root,
+                &container.workspace_root,
+            ),
+            (Mount::CargoHome, &host.cargo_home, &container.cargo_home),
+            (Mount::RustupHome, &host.rustup_home, &container.rustup_home),
+        ] {
+            volumes.push(engine_data.mount_volume(
+                mount,
+                host_path.as_str(),
+                container_path.as_str(),
+            )?);
+        }
+        volumes.extend(
+            container
+                .volumes
+                .iter()
+                .map(|volume| volume.to_arg(engine_data.selinux)),
+        );
+
+        if let Some(dir) = &self.runner_build {
+            volumes.push(
+                MountOptions::default()
+                    .with_selinux(engine_data.selinux)
+                    .volume(dir.as_str(), container.gungraun_source.as_str()),
+            );
+        } else if let Some(path) = host.runner() {
+            debug!("Using the gungraun-runner '{path}'");
+            volumes.push(engine_data.mount_volume(
+                Mount::Runner,
+                path.as_str(),
+                container.gungraun_runner.as_str(),
+            )?);
+        } else {
+            // The bootstrap script fails without a runner
+        }
+        if let Some(sysroot) = &host.sysroot {
+            // The toolchain is mounted at the same path to keep absolute paths working
+            volumes.push(
+                MountOptions::parse("ro", engine_data.engine)?
+                    .with_selinux(engine_data.selinux)
+                    .volume(sysroot.as_str(), sysroot.as_str()),
+            );
+        }
+        for extra in &host.extra_runners {
+            volumes.push(engine_data.mount_volume(
+                Mount::Runner,
+                host.gungraun_home.join(extra.cache_path()).as_str(),
+                extra.container_path().as_str(),
+            )?);
+        }
+
+        let mut env = vec![
+            format!("AR={gnu_triple}-ar"),
+            format!("CC={gnu_triple}-gcc"),
+            format!("LD={gnu_triple}-ld"),
+            format!(
+                "BINDGEN_EXTRA_CLANG_ARGS_{target_upper_env}=--sysroot={sysroot} \
+                 -idirafter/usr/include"
+            ),
+        ];
+
+        for (key, value) in &engine_data.envs.container {
+            env.push(format!("{key}={value}"));
+        }
+
+        env.extend([
+            format!("USER={}", container.user),
+            format!("{}={}", envs::CARGO_GUNGRAUN_UID, container.uid),
+            format!("{}={}", envs::CARGO_GUNGRAUN_GID, container.gid),
+            format!("HOME={}", container.home),
+            // TODO: EXTRA_PATH
+            format!(
+                "PATH={}{}/bin:/usr/local/sbin:/usr/local/bin:/usr/sbin:/usr/bin:/sbin:/bin",
+                host.sysroot
+                    .as_ref()
+                    .map_or_else(String::new, |sysroot| format!("{sysroot}/bin:")),
+                container.cargo_home
+            ),
+            format!("SHELL={}", container.shell),
+            format!("{}={}", envs::CARGO_HOME, container.cargo_home),
+            format!("{}={}", envs::RUSTUP_HOME, container.rustup_home),
+            format!("{}={}", envs::GUNGRAUN_HOME, container.gungraun_home),
+            format!("{}={}", envs::GUNGRAUN_RUNNER, container.gungraun_runner),
+            format!(
+                "{}={}",
+                envs::GUNGRAUN_SEPARATE_TARGETS,
+                container.separate_targets
+            ),
+            format!("{}={}", envs::GUNGRAUN_VERSION, host.gungraun_version),
+            format!("{}={}", envs::CARGO_TARGET_DIR, container.target_dir),
+            format!(
+                "CARGO_TARGET_{target_upper_env}_RUNNER={}",
+                container.runner
+            ),
+            format!("CARGO_TARGET_{target_upper_env}_LINKER={gnu_triple}-gcc"),
+            format!("{}={sysroot}", envs::QEMU_LD_PREFIX),
+        ]);
+        if let Some(toolchain) = &host.toolchain {
+            env.push(format!("{}={toolchain}", envs::RUSTUP_TOOLCHAIN));
+        }
+        if !host.extra_runners.is_empty() {
+            let runners = host
+                .extra_runners
+                .iter()
+                .flat_map(|extra| {
+                    extra
+                        .benches
+                        .iter()
+                        .map(move |bench| format!("{bench}={}", extra.container_path()))
+                })
+                .collect::<Vec<_>>();
+            env.push(format!(
+                "{}={}",
+                envs::CARGO_GUNGRAUN_RUNNERS,
+                runners.join(" ")
+            ));
+        }
+        if self.runner_build.is_some() {
+            env.push(format!(
+                "{}={}",
+                envs::CARGO_GUNGRAUN_RUNNER_SOURCE,
+                container.gungraun_source
+            ));
+        }
+
+        // The volumes are shared into the guest with virtfs
+        let qemu_mounts = container
+            .volumes
+            .iter()
+            .enumerate()
+            .map(|(index, volume)| {
+                let mut mount = format!("volume{index}:{}", volume.container);
+                if volume.options.is_readonly() {
+                    mount.push_str(":ro");
+                }
+                mount
+            })
+            .collect::<Vec<_>>();
+        if !qemu_mounts.is_empty() {
+            env.push(format!(
+                "{}={}",
+                envs::CARGO_GUNGRAUN_QEMU_MOUNTS,
+                qemu_mounts.join(" ")
+            ));
+        }
+
+        Ok(RunSpec {
+            cmd: vec!["/bootstrap.sh".to_owned()],
+            env,
+            image: engine_data.image.clone(),
+            labels: vec![
+                (
+                    labels::VERSION.to_owned(),
+                    CARGO_GUNGRAUN_VERSION.to_owned(),
+                ),
+                (labels::TARGET.to_owned(), target.to_string()),
+                (
+                    labels::WORKSPACE.to_owned(),
+                    host.workspace_root.to_string(),
+                ),
+                (labels::PID.to_owned(), std::process::id().to_string()),
+                (labels::PID_NAMESPACE.to_owned(), pid_namespace()),
+            ],
+            name: container.name.clone(),
+            privileged: engine_data.has_accelerator(),
+            pull: !self.offline,
+            seccomp,
+            security_opts: engine_data
+                .apparmor
+                .iter()
+                .map(|profile| format!("apparmor={profile}"))
+                .collect(),
+            // The bootstrap script needs root to set up the user. Commands are executed as the
+            // container user.
+            user: "root".to_owned(),
+            userns: engine_data.userns.clone(),
+            volumes,
+        })
+    }
+
+    /// Write the environment variables forwarded into the guest to the env file
+    fn write_env_file(&self) -> Result<()> {
+        let path = self.host.gungraun_home.join(self.env_file_name());
+        env_file::write(&path, &self.engine_data.envs.guest)
+    }
+}
+
+/// Run the benchmarks with the `cargo_args` in a container for the `target`
+///
+/// If [`Options::keep`] is true, the container is kept running for reuse by later invocations.
 ///
 /// # Errors
 ///
-/// # Panics
-#[allow(clippy::too_many_lines)]
-pub fn run_bench(target: Target, cargo_args: Vec<OsString>) -> Result<()> {
-    let host = HostData::new()?;
-    let container = ContainerData::new(&host)?;
-    let engine_data = EngineData::new(target, &host)?;
-
-    let target_upper_env = target.to_upper_env();
-    let gnu_triple = target.to_gnu_triple();
-    let sysroot = format!("/usr/{gnu_triple}");
-
-    let seccomp = include_str!("seccomp.json");
-    let mut file =
-        File::create(&engine_data.seccomp_path).with_context(|| "Failed to create seccomp.json")?;
-    file.write_all(seccomp.as_bytes())
-        .with_context(|| "Failed to write to seccomp.json")?;
-
-    std::process::Command::new("rustup")
-        .args(["target", "add", &target.to_string()])
-        .status()
-        .map_err(Error::CommandSpawn)
-        .and_then(|status| {
-            if status.success() {
-                Ok(())
-            } else {
-                Err(Error::Command(status))
+/// If starting the container or running the benchmarks failed
+pub fn run_bench(target: Target, cargo_args: Vec<OsString>, options: &Options) -> Result<()> {
+    let session = Session::start(target, options)?;
+    let result = session.exec_bench(cargo_args, &options.selection);
+    let finished = session.finish();
+    result.and(finished)
+}
+
+/// Parse the lines `BENCH\tHARNESS\tRUN\tCODE` recorded by the runner
+///
+/// Invalid lines are ignored.
+#[must_use]
+pub fn parse_summary(content: &str) -> Vec<SummaryRecord> {
+    content
+        .lines()
+        .filter_map(|line| {
+            let mut fields = line.splitn(4, '\t');
+            Some(SummaryRecord {
+                bench: fields.next()?.to_owned(),
+                harness: fields.next()?.to_owned(),
+                run: fields.next()?.to_owned(),
+                code: fields.next()?.parse().ok()?,
+            })
+        })
+        .collect()
+}
+
+/// The message summarizing the `records` of the runner
+///
+/// Returns `None` if nothing was recorded.
+#[must_use]
+pub fn summary_message(records: &[SummaryRecord]) -> Option<String> {
+    let mut message = String::new();
+    for record in records {
+        let run = match record.run.as_str() {
+            "gungraun" => "ran with qemu-user and the gungraun-runner in the qemu guest",
+            "qemu-user" => "ran with qemu-user",
+            "vm" => "ran in the qemu guest",
+            "skip" => "skipped",
+            "error" => "refused by the policy 'error'",
+            other => other,
+        };
+        write!(
+            message,
+            "\n  - {} ({}): {run}",
+            record.bench, record.harness
+        )
+        .unwrap();
+        if record.code != 0 && record.run != "error" {
+            write!(message, " and failed with exit code {}", record.code).unwrap();
+        }
+    }
+
+    if message.is_empty() {
+        None
+    } else {
+        Some(format!("Summary of the benches:{message}"))
+    }
+}
+
+/// The typed [`Error`] for the `error` of `cargo bench` with the `records` of the runner
+///
+/// The first failed bench decides. If no bench failed, building the benches failed.
+fn bench_error(error: anyhow::Error, records: &[SummaryRecord]) -> anyhow::Error {
+    if let Some(record) = records.iter().find(|r| r.code != 0) {
+        return match record.run.as_str() {
+            "error" => Error::Config(format!(
+                "The bench '{}' ({}) doesn't use gungraun and the policy is 'error'",
+                record.bench, record.harness
+            )),
+            _ if record.code == REGRESSION_EXIT_CODE && record.harness == "gungraun" => {
+                Error::Regression(record.bench.clone())
             }
-        })?;
-
-    let mut up_command = Command::new(engine_data.engine)?;
-    up_command.arg("run");
-
-    match engine_data.engine {
-        // TODO: This flag will ignore the PODMAN_USERNS environment variable
-        Engine::Podman => {
-            up_command.arg("--userns=host");
-        }
-        Engine::Docker => {} // TODO: implement
-    }
-
-    up_command.arg(format!(
-        "--security-opt=seccomp={}",
-        &engine_data.seccomp_path
-    ));
-    up_command.args(["--name", &container.name, "--rm"]);
-    up_command.args([
-        "--volume",
-        &format!("{}:{}", host.gungraun_home, container.gungraun_home),
-        "--volume",
-        &format!("{}:{}", host.target_dir, container.target_dir),
-        "--volume",
-        &format!("{}:{}", host.workspace_root, container.workspace_root),
-        "--volume",
-        &format!("{}:{}", host.cargo_home, container.cargo_home),
-        "--volume",
-        &format!("{}:{}", host.rustup_home, container.rustup_home),
-    ]);
-    for volume in &engine_data.volumes {
-        up_command.args(["--volume", volume]);
-    }
-
-    if let Some(path) = host.gungraun_runner {
-        debug!("Found {}. Using '{path}'", envs::GUNGRAUN_RUNNER);
-        up_command.args([
-            "--volume",
-            &format!("{}:{}:exec,ro", path, container.gungraun_runner),
-        ]);
+            _ => Error::Benchmark {
+                bench: Some(record.bench.clone()),
+                code: record.code,
+            },
+        }
+        .into();
+    }
+
+    match error.downcast_ref::<Error>() {
+        Some(Error::Command(status)) => Error::Build(*status).into(),
+        _ => error,
     }
+}
+
+/// Run the command `argv` in a container for the `target`
+///
+/// The command runs in the same environment as the benchmarks, so the runner of the target is
+/// configured and, for example, `cargo run` executes the binary in the qemu guest.
+///
+/// # Errors
+///
+/// If starting the container or running the command failed
+pub fn exec(target: Target, argv: &[OsString], options: &Options) -> Result<()> {
+    let session = Session::start(target, options)?;
+    let result = session.exec(argv, true);
+    let finished = session.finish();
+    result.and(finished)
+}
+
+/// Open an interactive shell in a container for the `target`
+///
+/// The shell runs in the same environment as the benchmarks. If `guest` is true, the shell is
+/// opened in the booted qemu guest instead of the container.
+///
+/// # Errors
+///
+/// If starting the container or the shell failed
+pub fn shell(target: Target, guest: bool, options: &Options) -> Result<()> {
+    let session = Session::start(target, options)?;
+    let result = session.exec_shell(guest);
+    let finished = session.finish();
+    result.and(finished)
+}
+
+/// Start a kept container for the `target` and boot the qemu guest
+///
+/// # Errors
+///
+/// If starting the container or booting the guest failed
+pub fn up(target: Target, options: &Options) -> Result<()> {
+    let options = Options {
+        keep: true,
+        ..options.clone()
+    };
+    let session = Session::start(target, &options)?;
+    session.boot_vm()?;
+    session.finish()
+}
+
+/// Stop the kept container of the current workspace and the `target`
+///
+/// # Errors
+///
+/// If stopping the container failed
+pub fn down(target: Target, options: &Options) -> Result<()> {
+    let backend = Backend::from_env(Engine::from_env()?)?;
+    let host = HostData::new(
+        options.offline,
+        options.manifest_path.as_deref(),
+        options.toolchain.as_deref(),
+    )?;
+    let name = kept_container_name(&host.workspace_root, target);
+
+    if backend.inspect(&name)?.is_some() {
+        info!("Stopping the kept container '{name}'");
+        backend.remove(&name)
+    } else {
+        info!("No kept container found for target '{target}'");
+        Ok(())
+    }
+}
 
-    up_command.args([
-        "--env",
-        &format!("AR={gnu_triple}-ar"),
-        "--env",
-        &format!("CC={gnu_triple}-gcc"),
-        "--env",
-        &format!("LD={gnu_triple}-ld"),
-        "--env",
-        &format!(
-            "BINDGEN_EXTRA_CLANG_ARGS_{target_upper_env}=--sysroot={sysroot} -idirafter/usr/include"
-        ),
-    ]);
-
-    let mut extra_envs = String::new();
-    for (key, value) in &engine_data.envs {
-        let env = format!("{key}={value}");
-        up_command.args(["--env", &env]);
-        write!(extra_envs, "{env} ").unwrap();
-    }
-
-    up_command.args([
-        "--env",
-        &format!("USER={}", container.user),
-        "--env",
-        &format!("HOME={}", container.home),
-        "--env",
-        // TODO: EXTRA_PATH
-        &format!(
-            "PATH={}/bin:/usr/local/sbin:/usr/local/bin:/usr/sbin:/usr/bin:/sbin:/bin",
-            container.cargo_home
-        ),
-        "--env",
-        &format!("SHELL={}", container.shell),
-        "--env",
-        &format!("{}={}", envs::CARGO_HOME, container.cargo_home),
-        "--env",
-        &format!("{}={}", envs::RUSTUP_HOME, container.rustup_home),
-        "--env",
-        &format!("{}={}", envs::GUNGRAUN_HOME, container.gungraun_home),
-        "--env",
-        &format!("{}={}", envs::GUNGRAUN_RUNNER, container.gungraun_runner),
-        "--env",
-        &format!(
-            "{}={}",
-            envs::GUNGRAUN_SEPARATE_TARGETS,
-            container.separate_targets
-        ),
-        "--env",
-        &format!("{}={}", envs::GUNGRAUN_VERSION, host.gungraun_version),
-        "--env",
-        &format!("{}={}", envs::CARGO_TARGET_DIR, container.target_dir),
-        "--env",
-        &format!(
-            "CARGO_TARGET_{target_upper_env}_RUNNER={}",
-            container.runner
-        ),
-        "--env",
-        &format!("CARGO_TARGET_{target_upper_env}_LINKER={gnu_triple}-gcc",),
-        "--env",
-        &format!("{}={sysroot}", envs::QEMU_LD_PREFIX),
-    ]);
-
-    if engine_data.has_accelerator() {
-        up_command.arg("--privileged");
-    }
-
-    up_command.args([&engine_data.image, "/bootstrap.sh"]);
-
-    debug!("Running up cmd: {up_command:?}");
-    let mut up_child = up_command
+/// Return true if the standard library of the `target` is installed in the `sysroot`
+#[must_use]
+pub fn is_target_installed(target: Target, sysroot: &Utf8Path) -> bool {
+    sysroot
+        .join("lib/rustlib")
+        .join(target.to_string())
+        .join("lib")
+        .is_dir()
+}
+
+/// The host name and, if available, the pid namespace of the current process
+///
+/// The process ids of the [`labels::PID`] label can only be checked by processes with the same
+/// pid namespace on the same host.
+#[must_use]
+pub fn pid_namespace() -> String {
+    let host = std::process::Command::new("uname")
+        .arg("-n")
+        .run_with_output()
+        .map(|output| output.trim().to_owned())
+        .unwrap_or_default();
+    match std::fs::read_link("/proc/self/ns/pid") {
+        Ok(namespace) => format!("{host}:{}", namespace.display()),
+        Err(_) => host,
+    }
+}
+
+/// The name of the kept container of the workspace and target
+#[must_use]
+pub fn kept_container_name(workspace_root: &Utf8Path, target: Target) -> String {
+    let key = format!("{workspace_root}\0{target}");
+    format!(
+        "{CONTAINER_NAME_PREFIX}{}",
+        hex::encode(fnv1a(key.as_bytes()).to_be_bytes())
+    )
+}
+
+/// The 64-bit FNV-1a hash which is stable across versions and platforms
+#[must_use]
+pub fn fnv1a(bytes: &[u8]) -> u64 {
+    bytes.iter().fold(0xcbf2_9ce4_8422_2325, |hash, byte| {
+        (hash ^ u64::from(*byte)).wrapping_mul(0x0100_0000_01b3)
+    })
+}
+
+/// Wait for the bootstrap script to finish by following the logs of the container `name`
+fn wait_for_logs(engine: Engine, name: &str) -> Result<()> {
+    let mut logs_child = Command::new(engine)?
+        .args(["logs", "--follow", name])
         // TODO: CHECK or make configurable via `--nocapture` or similar
         .stdout(Stdio::piped())
         .stderr(Stdio::null())
         .spawn()
-        .with_context(|| "Spawning the container should succeed")?;
-
-    if let Some(stdout) = up_child.stdout.take() {
-        let mut reader = BufReader::new(stdout);
-        loop {
-            let mut buffer = String::new();
-            match reader.read_line(&mut buffer) {
-                Ok(_) => {}
-                Err(error) => {
-                    up_child
-                        .kill()
-                        .expect("Killing the up child process should succeed");
-                    up_child
-                        .wait()
-                        .expect("Waiting for the up child process should succeed");
-                    return Err(anyhow!("Failed to execute the run command: '{error}'"));
-                }
-            }
+        .map_err(Error::CommandSpawn)?;
 
-            if buffer.trim() == "cargo-gungraun: bootstrap finished" {
+    let stdout = logs_child
+        .stdout
+        .take()
+        .expect("The stdout of the logs process should be piped");
+    let mut reader = BufReader::new(stdout);
+    let mut buffer = String::new();
+    let result = loop {
+        buffer.clear();
+        match reader.read_line(&mut buffer) {
+            Ok(0) => break Err(Error::Bootstrap(name.to_owned()).into()),
+            Ok(_) if buffer.trim() == BOOTSTRAP_FINISHED => {
                 debug!("bootstrap script succeeded");
-                break;
-            }
-
-            // Avoid a possible hot loop waiting some milliseconds
-            sleep(Duration::from_millis(100));
-            match up_child.try_wait() {
-                Ok(Some(status)) => {
-                    return Err(anyhow!(
-                        "Failed executing the container engine. Child returned with: '{status}'"
-                    ))
-                }
-                Ok(None) => {}
-                Err(error) => {
-                    return Err(anyhow!("Failed to wait for the child process: '{error}'"))
-                }
+                break Ok(());
             }
+            Ok(_) => {}
+            Err(error) => break Err(anyhow!("Failed to read the container logs: '{error}'")),
         }
-    } else {
-        up_child
-            .kill()
-            .expect("Killing the up child process should succeed");
-        up_child
-            .wait()
-            .expect("Waiting for the up child process should succeed");
-        return Err(anyhow!("Expected a stdout handle of the up child process"));
-    }
-
-    // TODO: The log-file doesn't have any effect, and neither the others below
-    let mut executor_args = format!(
-        "--qemu-arch {target} --log-file {}/qemu.log",
-        container.gungraun_home
+    };
+
+    // The logs process follows the container until it stops, so it has to be killed
+    logs_child.kill().map_err(Error::CommandSpawn)?;
+    logs_child.wait().map_err(Error::CommandSpawn)?;
+
+    result
+}
+
+#[cfg(test)]
+mod tests {
+    use rstest::rstest;
+
+    use super::*;
+
+    #[rstest]
+    #[case::podman("podman", Engine::Podman)]
+    #[case::podman_remote("podman-remote", Engine::PodmanRemote)]
+    #[case::docker("docker", Engine::Docker)]
+    #[case::nerdctl("nerdctl", Engine::Nerdctl)]
+    fn engine_try_from_when_valid(#[case] value: &str, #[case] expected: Engine) {
+        assert_eq!(Engine::try_from(OsStr::new(value)).unwrap(), expected);
+    }
+
+    #[test]
+    fn engine_try_from_when_invalid() {
+        assert_eq!(
+            Engine::try_from(OsStr::new("lxc")).unwrap_err().to_string(),
+            "Invalid container engine: 'lxc'. Expected one of 'podman', 'podman-remote', 'docker' \
+             or 'nerdctl'"
+        );
+    }
+
+    #[rstest]
+    #[case::empty("", 0xcbf2_9ce4_8422_2325)]
+    #[case::a("a", 0xaf63_dc4c_8601_ec8c)]
+    #[case::foobar("foobar", 0x8594_4171_f739_67e8)]
+    fn fnv1a_hash(#[case] input: &str, #[case] expected: u64) {
+        assert_eq!(fnv1a(input.as_bytes()), expected);
+    }
+
+    /// The recorded lines of the runner with an invalid line in between
+    const SUMMARY: &str = concat!(
+        "bench_a\tgungraun\tgungraun\t0\n",
+        "criterion\tcriterion\tskip\t0\n",
+        "invalid\n",
+        "bench_b\tgungraun\tgungraun\t3\n"
     );
 
-    // TODO: add other envs,
-    // TODO: QEMU_EXTRA_ARGS, QEMU_MAX_MEM, QEMU_MIN_MEM, QEMU_MEM, same with cpus
-    // TODO: QEMU_LOG_FILE
+    #[test]
+    fn summary_message_when_recorded() {
+        assert_eq!(
+            summary_message(&parse_summary(SUMMARY)).unwrap(),
+            "Summary of the benches:\n  - bench_a (gungraun): ran with qemu-user and the \
+             gungraun-runner in the qemu guest\n  - criterion (criterion): skipped\n  - bench_b \
+             (gungraun): ran with qemu-user and the gungraun-runner in the qemu guest and failed \
+             with exit code 3"
+        );
+    }
 
-    if log_enabled!(log::Level::Trace) {
-        write!(executor_args, " --debug trace").unwrap();
-    } else if log_enabled!(log::Level::Debug) {
-        write!(executor_args, " --debug debug").unwrap();
-    } else {
-        // do nothing
-    }
-
-    if !extra_envs.is_empty() {
-        write!(executor_args, " --envs '{extra_envs}'").unwrap();
-    }
-
-    let mut exec_command = Command::new(engine_data.engine)?;
-    exec_command.args([
-        "exec",
-        "--workdir",
-        container.current_dir.as_str(),
-        "--env",
-        &format!("{}={}", envs::GUNGRAUN_EXECUTOR, container.qemu_runner),
-        "--env",
-        &format!("{}={}", envs::GUNGRAUN_EXECUTOR_ARGS, executor_args),
-        // TODO: CLEANUP, doesn't work. Error message is
-        // dbclient: Failed reading termmodes
-        //
-        // dbclient: Connection to root@localhost:10022 exited: Failed to set raw TTY mode
-        // "--env",
-        // "GUNGRAUN_NOCAPTURE=yes",
-        "--env",
-        "GUNGRAUN_LOG=warn",
-    ]);
-
-    if let Some(accel) = engine_data.accelerator {
-        exec_command.args([
-            "--env",
-            &format!("{}={accel}", envs::CARGO_GUNGRAUN_QEMU_ACCELERATOR),
-        ]);
+    #[test]
+    fn summary_message_when_empty() {
+        assert_eq!(summary_message(&parse_summary("")), None);
     }
 
-    for env in [
-        envs::CARGO_GUNGRAUN_QEMU_TIMEOUT,
-        envs::CARGO_GUNGRAUN_QEMU_EXTRA_ARGS,
-    ] {
-        if let Ok(value) = std::env::var(env) {
-            exec_command.args(["--env", &format!("{env}={value}")]);
-        }
+    #[rstest]
+    #[case::regression(SUMMARY, 3)]
+    #[case::benchmark("bench\tcriterion\tqemu-user\t3\n", 3)]
+    #[case::benchmark_other_code("bench\tgungraun\tgungraun\t42\n", 42)]
+    #[case::policy("bench\tlibtest\terror\t1\n", 10)]
+    #[case::build("", 15)]
+    fn bench_error_exit_code(#[case] summary: &str, #[case] expected: i32) {
+        use std::os::unix::process::ExitStatusExt;
+
+        let error =
+            anyhow::Error::from(Error::Command(std::process::ExitStatus::from_raw(101 << 8)));
+        let error = bench_error(error, &parse_summary(summary));
+        assert_eq!(Error::exit_code_of(&error), expected);
     }
 
-    if io::stdin().is_terminal() && io::stdout().is_terminal() && io::stderr().is_terminal() {
-        exec_command.arg("-t");
+    #[test]
+    fn kept_container_name_differs_by_target() {
+        let workspace = Utf8Path::new("/some/workspace");
+        let name = kept_container_name(workspace, Target::S390x_Unknown_Linux_Gnu);
+
+        assert!(name.starts_with(CONTAINER_NAME_PREFIX));
+        assert_eq!(
+            name,
+            kept_container_name(workspace, Target::S390x_Unknown_Linux_Gnu)
+        );
+        assert_ne!(
+            name,
+            kept_container_name(workspace, Target::Aarch64_Unknown_Linux_Gnu)
+        );
     }
 
-    exec_command.args([&container.name, "cargo", "bench"]);
-    exec_command.args(cargo_args);
-    // TODO: revert TEST
-    // exec_command.args(["-i", &container.name, "/bin/bash"]);
+    /// Run the `docker/runner.sh` for the executable `bin` with stubs of qemu and the qemu
+    /// runner
+    ///
+    /// The stubs log their arguments and the run in the guest exits with `5`. Returns the exit
+    /// code and the log.
+    fn run_runner_script(name: &str, bin: &str, benches: Option<&str>) -> (Option<i32>, String) {
+        let dir = Utf8PathBuf::try_from(std::env::temp_dir())
+            .unwrap()
+            .join(format!("cargo-gungraun-test-{}.{name}", std::process::id()));
+        std::fs::create_dir_all(dir.join("bin")).unwrap();
+        let log = dir.join("log");
+        let write_script = |path: Utf8PathBuf, content: &str| {
+            std::fs::write(&path, content).unwrap();
+            std::process::Command::new("chmod")
+                .arg("+x")
+                .arg(&path)
+                .run()
+                .unwrap();
+        };
+        write_script(dir.join("lib.sh"), include_str!("../../docker/lib.sh"));
+        write_script(
+            dir.join("qemu_runner.sh"),
+            "#!/bin/sh\necho \"vm $*\" >>\"$LOG\"\ncase \" $* \" in *\" --no-run \"*) exit 0 ;; \
+             esac\nexit 5\n",
+        );
+        write_script(
+            dir.join("bin/qemu-x86_64"),
+            "#!/bin/sh\necho \"qemu-user $*\" >>\"$LOG\"\n",
+        );
+        write_script(
+            dir.join("runner.sh"),
+            &include_str!("../../docker/runner.sh")
+                .replace("/lib.sh", dir.join("lib.sh").as_str())
+                .replace("/qemu_runner.sh", dir.join("qemu_runner.sh").as_str()),
+        );
 
-    debug!("Running the exec command: {exec_command:?}");
-    exec_command
-        .status()
-        .map_err(Error::CommandSpawn)
-        .and_then(|status| {
-            if status.success() {
-                Ok(())
-            } else {
-                Err(Error::Command(status))
-            }
-        })?;
+        let mut command = std::process::Command::new("bash");
+        command
+            .arg(dir.join("runner.sh"))
+            .args([bin, "arg"])
+            .env("LOG", &log)
+            .env(
+                "PATH",
+                format!("{}:{}", dir.join("bin"), std::env::var("PATH").unwrap()),
+            )
+            .env(envs::CARGO_GUNGRAUN_TARGET, "x86_64-unknown-linux-gnu")
+            .env_remove(envs::CARGO_GUNGRAUN_NON_GUNGRAUN)
+            .env_remove(envs::CARGO_GUNGRAUN_RUNNERS)
+            .env_remove(envs::CARGO_GUNGRAUN_SUMMARY_FILE)
+            .env_remove(envs::GUNGRAUN_LOG)
+            .stderr(Stdio::null());
+        match benches {
+            Some(benches) => command.env(envs::CARGO_GUNGRAUN_BENCHES, benches),
+            None => command.env_remove(envs::CARGO_GUNGRAUN_BENCHES),
+        };
+        let status = command.status().unwrap();
+        let log = std::fs::read_to_string(&log).unwrap_or_default();
+        std::fs::remove_dir_all(&dir).unwrap();
 
-    // TODO: Instead of Stdio::null route to a log file
-    debug!("Stopping the container '{}' ...", &container.name);
-    Command::new(engine_data.engine)?
-        .args(["stop", "--ignore", &container.name])
-        .stdout(Stdio::null())
-        .stderr(Stdio::null())
-        .status()
-        .map_err(Error::CommandSpawn)
-        .and_then(|status| {
-            if status.success() {
-                Ok(())
-            } else {
-                Err(Error::Command(status))
-            }
-        })?;
+        (status.code(), log)
+    }
+
+    #[test]
+    fn runner_script_without_benches_runs_in_vm() {
+        let (code, log) = run_runner_script("exec", "/target/example-0123", None);
 
-    // The process should not run anymore but still wait to avoid zombies
-    up_child.wait().map_err(Error::CommandSpawn)?;
+        assert_eq!(code, Some(5));
+        assert!(
+            log.ends_with("vm --qemu-arch x86_64-unknown-linux-gnu -- /target/example-0123 arg\n")
+        );
+    }
 
-    Ok(())
+    #[test]
+    fn runner_script_with_benches_applies_policy() {
+        let (code, log) = run_runner_script(
+            "bench",
+            "/target/criterion-0123",
+            Some("criterion=criterion"),
+        );
+
+        assert_eq!(code, Some(0));
+        assert!(!log.contains("vm "));
+    }
+
+    #[test]
+    fn is_target_installed_in_sysroot() {
+        let sysroot = Utf8PathBuf::try_from(std::env::temp_dir())
+            .unwrap()
+            .join(format!(
+                "cargo-gungraun-test-{}.sysroot",
+                std::process::id()
+            ));
+        std::fs::create_dir_all(sysroot.join("lib/rustlib/s390x-unknown-linux-gnu/lib")).unwrap();
+
+        let installed = is_target_installed(Target::S390x_Unknown_Linux_Gnu, &sysroot);
+        let missing = is_target_installed(Target::Aarch64_Unknown_Linux_Gnu, &sysroot);
+        std::fs::remove_dir_all(&sysroot).unwrap();
+
+        assert!(installed);
+        assert!(!missing);
+    }
 }
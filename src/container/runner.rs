@@ -0,0 +1,490 @@
+//! The gungraun-runner mounted into the container
+//!
+//! The runner of a released gungraun version is downloaded once from the release page on the
+//! host, verified against the published sha256 checksum and stored in a versioned cache
+//! directory in the gungraun home. The runner of a git or path source of gungraun is built
+//! from the same source in the container and cached by the source and its revision.
+
+use std::process::Stdio;
+
+use anyhow::{anyhow, Context, Result};
+use camino::{Utf8Path, Utf8PathBuf};
+use log::{debug, info};
+use which::which;
+
+use super::{fnv1a, CommandExt};
+use crate::envs;
+use crate::meta::{CargoMetadata, HostData, Package};
+
+/// The base url of the gungraun releases
+pub const RELEASES_URL: &str = "https://github.com/gungraun/gungraun/releases/download";
+
+/// The runner of benches which use another gungraun version than the rest of the workspace
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ExtraRunner {
+    /// The names of the benches as in the file names of the bench executables
+    pub benches: Vec<String>,
+    /// The released gungraun version
+    pub version: String,
+}
+
+/// Where the gungraun-runner comes from
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum RunnerSource {
+    /// A git or path source of gungraun from which the runner is built
+    Local {
+        /// The hash of the source and its revision
+        key: String,
+        /// The manifest of the gungraun package
+        manifest: Utf8PathBuf,
+    },
+    /// A released version of gungraun
+    Release(String),
+}
+
+impl ExtraRunner {
+    /// The path of the cached runner relative to the gungraun home
+    #[must_use]
+    pub fn cache_path(&self) -> Utf8PathBuf {
+        RunnerSource::Release(self.version.clone()).cache_path()
+    }
+
+    /// The path of the runner in the container
+    #[must_use]
+    pub fn container_path(&self) -> Utf8PathBuf {
+        Utf8PathBuf::from(format!("/usr/bin/gungraun-runner-{}", self.version))
+    }
+}
+
+impl RunnerSource {
+    /// Detect the source of the runner from the gungraun `package` of `cargo metadata`
+    ///
+    /// The revision of a path source is the commit of the git repository it is contained in.
+    /// Uncommitted changes are not detected, so the cached runner has to be removed manually.
+    ///
+    /// # Errors
+    ///
+    /// If the source is not supported
+    pub fn from_package(package: &Package) -> Result<Self> {
+        let manifest = Utf8PathBuf::try_from(package.manifest_path.clone())
+            .with_context(|| "The manifest path of gungraun is not a valid utf8 path")?;
+
+        let source = match package.source.as_deref() {
+            Some(source) if source.starts_with("registry+") || source.starts_with("sparse+") => {
+                return Ok(Self::Release(package.version.clone()))
+            }
+            // The source includes the commit after the `#`
+            Some(source) if source.starts_with("git+") => source.to_owned(),
+            Some(source) => return Err(anyhow!("Unsupported source of gungraun: '{source}'")),
+            None => {
+                let dir = manifest.parent().unwrap_or(&manifest);
+                let revision = std::process::Command::new("git")
+                    .args(["-C", dir.as_str(), "rev-parse", "HEAD"])
+                    .stderr(Stdio::null())
+                    .output()
+                    .ok()
+                    .filter(|output| output.status.success())
+                    .map_or_else(
+                        || package.version.clone(),
+                        |output| String::from_utf8_lossy(&output.stdout).trim().to_owned(),
+                    );
+                format!("path+{dir}#{revision}")
+            }
+        };
+
+        debug!("Building the gungraun-runner from the source '{source}'");
+        Ok(Self::Local {
+            key: hex::encode(fnv1a(source.as_bytes()).to_be_bytes()),
+            manifest,
+        })
+    }
+
+    /// The path of the cached runner relative to the gungraun home
+    #[must_use]
+    pub fn cache_path(&self) -> Utf8PathBuf {
+        let dir = match self {
+            Self::Local { key, .. } => {
+                format!("source-{key}-{}-unknown-linux-gnu", std::env::consts::ARCH)
+            }
+            Self::Release(version) => archive_name(version),
+        };
+        Utf8PathBuf::from("runner")
+            .join(dir)
+            .join("gungraun-runner")
+    }
+}
+
+/// The name of the release archive of the runner without the extension
+///
+/// The runner runs in the container, which is always linux with the architecture of the host.
+#[must_use]
+pub fn archive_name(version: &str) -> String {
+    format!(
+        "gungraun-runner-v{version}-{}-unknown-linux-gnu",
+        std::env::consts::ARCH
+    )
+}
+
+/// Return the local runner and download it into the cache if necessary
+///
+/// The runner from [`envs::GUNGRAUN_RUNNER`] is used as is. Returns `None` if the runner has
+/// to be built from a [`RunnerSource::Local`] in the container.
+///
+/// # Errors
+///
+/// If downloading or verifying the runner failed
+pub fn ensure(host: &HostData) -> Result<Option<Utf8PathBuf>> {
+    for extra in &host.extra_runners {
+        let path = host.gungraun_home.join(extra.cache_path());
+        if !path.is_file() {
+            info!(
+                "Downloading gungraun-runner {} for the benches {}",
+                extra.version,
+                extra.benches.join(", ")
+            );
+            download(&extra.version, &path, false)?;
+        }
+    }
+
+    if let Some(path) = &host.gungraun_runner {
+        debug!("Found {}. Using '{path}'", envs::GUNGRAUN_RUNNER);
+        return Ok(Some(path.clone()));
+    }
+
+    let path = host.cached_runner();
+    if path.is_file() {
+        debug!("Using the cached gungraun-runner '{path}'");
+        return Ok(Some(path));
+    }
+
+    match &host.runner_source {
+        RunnerSource::Local { .. } => Ok(None),
+        RunnerSource::Release(version) => {
+            info!("Downloading gungraun-runner {version}");
+            download(version, &path, true)?;
+            Ok(Some(path))
+        }
+    }
+}
+
+/// Download the runner of the gungraun `version` and move it to `dest` after verification
+///
+/// The expected checksum is taken from [`envs::CARGO_GUNGRAUN_RUNNER_SHA256`] if it has one
+/// for the `version` or, if `primary`, a plain checksum. Otherwise, it's downloaded from the
+/// release page.
+///
+/// # Errors
+///
+/// If any download failed or the checksum doesn't match
+pub fn download(version: &str, dest: &Utf8Path, primary: bool) -> Result<()> {
+    let dir = dest
+        .parent()
+        .with_context(|| format!("Invalid path of the runner: '{dest}'"))?;
+    let tmp = dir.join(format!("download-{}", std::process::id()));
+    std::fs::create_dir_all(&tmp)
+        .with_context(|| format!("Failed to create the directory '{tmp}'"))?;
+
+    let result = download_to(version, &tmp, primary).and_then(|runner| {
+        std::fs::rename(&runner, dest)
+            .with_context(|| format!("Failed to move the gungraun-runner to '{dest}'"))
+    });
+    // Best effort. A leftover directory doesn't hurt
+    let _ = std::fs::remove_dir_all(&tmp);
+    result
+}
+
+/// The checksum for the runner `version` from the `value` of
+/// [`envs::CARGO_GUNGRAUN_RUNNER_SHA256`]
+///
+/// The `value` is a plain checksum, which only applies to the `primary` runner, or a comma
+/// separated list of `VERSION=SHA256`.
+///
+/// # Errors
+///
+/// If a checksum which applies to the `version` is invalid
+pub fn expected_checksum(value: &str, version: &str, primary: bool) -> Result<Option<String>> {
+    let mut expected = None;
+    for entry in value.split(',').map(str::trim).filter(|e| !e.is_empty()) {
+        match entry.split_once('=') {
+            Some((v, checksum)) if v.trim() == version => {
+                expected = Some(parse_checksum(checksum)?);
+            }
+            None if primary => expected = Some(parse_checksum(entry)?),
+            Some(_) | None => {}
+        }
+    }
+
+    Ok(expected)
+}
+
+/// Parse the hex encoded sha256 checksum from the content of a checksum file
+///
+/// The checksum files have the format of `sha256sum`: `<checksum>  <file name>`.
+///
+/// # Errors
+///
+/// If the content doesn't start with a valid sha256 checksum
+pub fn parse_checksum(content: &str) -> Result<String> {
+    let checksum = content.split_whitespace().next().unwrap_or_default();
+    if checksum.len() == 64 && checksum.bytes().all(|b| b.is_ascii_hexdigit()) {
+        Ok(checksum.to_ascii_lowercase())
+    } else {
+        Err(anyhow!("Invalid sha256 checksum: '{checksum}'"))
+    }
+}
+
+/// The root directory of the workspace of the gungraun `manifest` which contains the runner
+///
+/// # Errors
+///
+/// If the workspace doesn't contain the gungraun-runner package
+pub fn source_workspace(manifest: &Utf8Path) -> Result<Utf8PathBuf> {
+    let metadata = CargoMetadata::with_args(&["--no-deps", "--manifest-path", manifest.as_str()])?;
+    let workspace_root = Utf8PathBuf::try_from(metadata.workspace_root)
+        .with_context(|| "The workspace root of gungraun is not a valid utf8 path")?;
+
+    if metadata
+        .packages
+        .iter()
+        .any(|p| p.name == "gungraun-runner")
+    {
+        Ok(workspace_root)
+    } else {
+        Err(anyhow!(
+            "Unable to build the gungraun-runner: The package was not found in the workspace \
+             '{workspace_root}' of gungraun. Set {} to the path of a gungraun-runner instead",
+            envs::GUNGRAUN_RUNNER
+        ))
+    }
+}
+
+/// Compute the sha256 checksum of the file at `path` with `sha256sum`
+///
+/// # Errors
+///
+/// If `sha256sum` could not be executed
+pub fn sha256(path: &Utf8Path) -> Result<String> {
+    std::process::Command::new("sha256sum")
+        .arg(path)
+        .run_with_output()
+        .with_context(|| format!("Failed to compute the checksum of '{path}'"))
+        .and_then(|output| parse_checksum(&output))
+}
+
+fn download_to(version: &str, dir: &Utf8Path, primary: bool) -> Result<Utf8PathBuf> {
+    let archive = archive_name(version);
+    let url = format!("{RELEASES_URL}/v{version}/{archive}.tar.gz");
+    let archive_path = dir.join(format!("{archive}.tar.gz"));
+    fetch(&url, &archive_path)?;
+
+    let configured = std::env::var(envs::CARGO_GUNGRAUN_RUNNER_SHA256)
+        .ok()
+        .map(|value| expected_checksum(&value, version, primary))
+        .transpose()
+        .with_context(|| format!("Invalid {}", envs::CARGO_GUNGRAUN_RUNNER_SHA256))?
+        .flatten();
+    let expected = if let Some(checksum) = configured {
+        checksum
+    } else {
+        let checksum_path = dir.join(format!("{archive}.tar.gz.sha256"));
+        fetch(&format!("{url}.sha256"), &checksum_path).with_context(|| {
+            format!(
+                "Failed to download the checksum of the gungraun-runner. Set {}={version}=<SHA256> \
+                 with the expected sha256 checksum of '{url}' to verify it anyway",
+                envs::CARGO_GUNGRAUN_RUNNER_SHA256
+            )
+        })?;
+        let content = std::fs::read_to_string(&checksum_path)
+            .with_context(|| format!("Failed to read '{checksum_path}'"))?;
+        parse_checksum(&content).with_context(|| format!("Invalid checksum file of '{url}'"))?
+    };
+
+    let actual = sha256(&archive_path)?;
+    if actual != expected {
+        return Err(anyhow!(
+            "The checksum of '{url}' doesn't match: Expected '{expected}' but was '{actual}'"
+        ));
+    }
+    debug!("Verified the checksum of '{url}': {actual}");
+
+    std::process::Command::new("tar")
+        .args(["xzf", archive_path.as_str(), "--strip-components=1", "-C"])
+        .arg(dir)
+        .arg(format!("{archive}/gungraun-runner"))
+        .stdout(Stdio::null())
+        .run()
+        .with_context(|| format!("Failed to extract the gungraun-runner from '{archive_path}'"))?;
+
+    Ok(dir.join("gungraun-runner"))
+}
+
+/// Download the `url` to the file `dest` with curl or wget
+fn fetch(url: &str, dest: &Utf8Path) -> Result<()> {
+    debug!("Downloading '{url}'");
+    let mut command = if which("curl").is_ok() {
+        let mut command = std::process::Command::new("curl");
+        command.args([
+            "--fail",
+            "--silent",
+            "--show-error",
+            "--location",
+            "--output",
+        ]);
+        command
+    } else {
+        let mut command = std::process::Command::new("wget");
+        command.args(["--quiet", "--output-document"]);
+        command
+    };
+
+    command
+        .arg(dest)
+        .arg(url)
+        .run()
+        .with_context(|| format!("Failed to download '{url}'"))
+}
+
+#[cfg(test)]
+mod tests {
+    use rstest::rstest;
+
+    use super::*;
+
+    fn package(source: Option<&str>) -> Package {
+        Package {
+            id: "gungraun 0.18.0".to_owned(),
+            manifest_path: "/path/to/gungraun/Cargo.toml".into(),
+            name: "gungraun".to_owned(),
+            source: source.map(ToOwned::to_owned),
+            version: "0.18.0".to_owned(),
+            ..Package::default()
+        }
+    }
+
+    #[rstest]
+    #[case::registry("registry+https://github.com/rust-lang/crates.io-index")]
+    #[case::sparse("sparse+https://index.crates.io/")]
+    fn runner_source_when_release(#[case] source: &str) {
+        assert_eq!(
+            RunnerSource::from_package(&package(Some(source))).unwrap(),
+            RunnerSource::Release("0.18.0".to_owned())
+        );
+    }
+
+    #[test]
+    fn runner_source_when_git() {
+        let first = RunnerSource::from_package(&package(Some(
+            "git+https://github.com/gungraun/gungraun?branch=main#0123abc",
+        )))
+        .unwrap();
+        let second = RunnerSource::from_package(&package(Some(
+            "git+https://github.com/gungraun/gungraun?branch=main#4567def",
+        )))
+        .unwrap();
+
+        assert!(matches!(first, RunnerSource::Local { .. }));
+        assert_ne!(first.cache_path(), second.cache_path());
+    }
+
+    #[test]
+    fn runner_source_when_unsupported() {
+        assert_eq!(
+            RunnerSource::from_package(&package(Some("other+https://example.com")))
+                .unwrap_err()
+                .to_string(),
+            "Unsupported source of gungraun: 'other+https://example.com'"
+        );
+    }
+
+    #[test]
+    fn runner_source_cache_path_when_release() {
+        assert_eq!(
+            RunnerSource::Release("0.18.0".to_owned()).cache_path(),
+            Utf8PathBuf::from(format!(
+                "runner/gungraun-runner-v0.18.0-{}-unknown-linux-gnu/gungraun-runner",
+                std::env::consts::ARCH
+            ))
+        );
+    }
+
+    #[rstest]
+    #[case::sha256sum(
+        "E3B0C44298FC1C149AFBF4C8996FB92427AE41E4649B934CA495991B7852B855  file.tar.gz\n",
+        "e3b0c44298fc1c149afbf4c8996fb92427ae41e4649b934ca495991b7852b855"
+    )]
+    #[case::only_checksum(
+        "e3b0c44298fc1c149afbf4c8996fb92427ae41e4649b934ca495991b7852b855",
+        "e3b0c44298fc1c149afbf4c8996fb92427ae41e4649b934ca495991b7852b855"
+    )]
+    fn parse_checksum_when_valid(#[case] content: &str, #[case] expected: &str) {
+        assert_eq!(parse_checksum(content).unwrap(), expected);
+    }
+
+    #[rstest]
+    #[case::empty("", "Invalid sha256 checksum: ''")]
+    #[case::too_short("e3b0c442  file", "Invalid sha256 checksum: 'e3b0c442'")]
+    #[case::html("<html>", "Invalid sha256 checksum: '<html>'")]
+    fn parse_checksum_when_invalid(#[case] content: &str, #[case] expected: &str) {
+        assert_eq!(parse_checksum(content).unwrap_err().to_string(), expected);
+    }
+
+    const SHA_A: &str = "e3b0c44298fc1c149afbf4c8996fb92427ae41e4649b934ca495991b7852b855";
+    const SHA_B: &str = "0123456789abcdef0123456789abcdef0123456789abcdef0123456789abcdef";
+
+    #[rstest]
+    #[case::plain_primary(SHA_A, "0.18.0", true, Some(SHA_A))]
+    #[case::plain_extra_runner(SHA_A, "0.17.1", false, None)]
+    #[case::versions_primary(&format!("0.18.0={SHA_A}, 0.17.1={SHA_B}"), "0.18.0", true, Some(SHA_A))]
+    #[case::versions_extra_runner(&format!("0.18.0={SHA_A}, 0.17.1={SHA_B}"), "0.17.1", false, Some(SHA_B))]
+    #[case::versions_other(&format!("0.18.0={SHA_A}"), "0.17.1", false, None)]
+    fn expected_checksum_when(
+        #[case] value: &str,
+        #[case] version: &str,
+        #[case] primary: bool,
+        #[case] expected: Option<&str>,
+    ) {
+        assert_eq!(
+            expected_checksum(value, version, primary)
+                .unwrap()
+                .as_deref(),
+            expected
+        );
+    }
+
+    #[test]
+    fn expected_checksum_when_invalid() {
+        assert_eq!(
+            expected_checksum("0.17.1=abc", "0.17.1", false)
+                .unwrap_err()
+                .to_string(),
+            "Invalid sha256 checksum: 'abc'"
+        );
+    }
+
+    #[test]
+    fn sha256_of_empty_file() {
+        let path = Utf8PathBuf::try_from(std::env::temp_dir())
+            .unwrap()
+            .join(format!("cargo-gungraun-test-{}.empty", std::process::id()));
+        std::fs::write(&path, "").unwrap();
+
+        let actual = sha256(&path);
+        std::fs::remove_file(&path).unwrap();
+
+        assert_eq!(
+            actual.unwrap(),
+            "e3b0c44298fc1c149afbf4c8996fb92427ae41e4649b934ca495991b7852b855"
+        );
+    }
+
+    #[test]
+    fn archive_name_contains_version_and_linux_target() {
+        assert_eq!(
+            archive_name("0.17.1"),
+            format!(
+                "gungraun-runner-v0.17.1-{}-unknown-linux-gnu",
+                std::env::consts::ARCH
+            )
+        );
+    }
+}
@@ -0,0 +1,282 @@
+//! The seccomp profile of the containers
+
+use std::env::VarError;
+
+use anyhow::{anyhow, Context, Result};
+use camino::{Utf8Path, Utf8PathBuf};
+use serde_json::{json, Value};
+
+use crate::envs;
+
+/// The seccomp profile shipped with cargo-gungraun
+pub const EMBEDDED_PROFILE: &str = include_str!("seccomp.json");
+
+/// The configured seccomp mode of the containers
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Seccomp {
+    /// A user-supplied profile
+    Custom(Utf8PathBuf),
+    /// The default profile of the container engine
+    Default,
+    /// The profile embedded in cargo-gungraun (the default)
+    Embedded,
+    /// Run the containers without seccomp filtering
+    Unconfined,
+}
+
+/// The resolved seccomp profile passed to the container engine
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum SeccompProfile {
+    /// Don't pass any profile, so the engine uses its default profile
+    Default,
+    /// The profile file on the host
+    File(Utf8PathBuf),
+    /// Disable seccomp filtering
+    Unconfined,
+}
+
+impl Seccomp {
+    /// Select the seccomp mode from [`envs::CARGO_GUNGRAUN_SECCOMP`]
+    ///
+    /// The value is one of `embedded`, `default`, `unconfined` or the path to a profile.
+    ///
+    /// # Errors
+    ///
+    /// If the environment variable is not valid utf8 or the profile doesn't exist
+    pub fn from_env() -> Result<Self> {
+        match std::env::var(envs::CARGO_GUNGRAUN_SECCOMP) {
+            Ok(value) => Self::parse(&value),
+            Err(VarError::NotUnicode(_)) => Err(anyhow!(
+                "Invalid {}: Not utf8",
+                envs::CARGO_GUNGRAUN_SECCOMP
+            )),
+            Err(VarError::NotPresent) => Ok(Self::Embedded),
+        }
+    }
+
+    /// Resolve the [`SeccompProfile`] including the extra syscalls
+    ///
+    /// The embedded profile and custom profiles with extra syscalls are written to `path`.
+    ///
+    /// # Errors
+    ///
+    /// If writing the profile failed or extra syscalls are configured without a profile
+    pub fn resolve(&self, path: &Utf8Path) -> Result<SeccompProfile> {
+        let syscalls = extra_syscalls()?;
+        let profile = match self {
+            Self::Custom(custom) if syscalls.is_empty() => {
+                return Ok(SeccompProfile::File(custom.clone()))
+            }
+            Self::Custom(custom) => std::fs::read_to_string(custom)
+                .with_context(|| format!("Failed to read the seccomp profile '{custom}'"))?,
+            Self::Embedded => EMBEDDED_PROFILE.to_owned(),
+            Self::Default | Self::Unconfined if !syscalls.is_empty() => {
+                return Err(anyhow!(
+                    "{} requires the embedded or a custom seccomp profile",
+                    envs::CARGO_GUNGRAUN_SECCOMP_SYSCALLS
+                ))
+            }
+            Self::Default => return Ok(SeccompProfile::Default),
+            Self::Unconfined => return Ok(SeccompProfile::Unconfined),
+        };
+
+        let profile = if syscalls.is_empty() {
+            profile
+        } else {
+            merge(&profile, &syscalls)?
+        };
+        std::fs::write(path, profile)
+            .with_context(|| format!("Failed to write the seccomp profile to '{path}'"))?;
+
+        Ok(SeccompProfile::File(path.to_owned()))
+    }
+
+    fn parse(value: &str) -> Result<Self> {
+        match value {
+            "embedded" | "" => Ok(Self::Embedded),
+            "default" => Ok(Self::Default),
+            "unconfined" => Ok(Self::Unconfined),
+            path => {
+                let path = Utf8Path::new(path);
+                path.canonicalize_utf8().map(Self::Custom).with_context(|| {
+                    format!(
+                        "Invalid {}: Expected one of 'embedded', 'default', 'unconfined' or the \
+                         path to a seccomp profile but the file '{path}' was not found",
+                        envs::CARGO_GUNGRAUN_SECCOMP
+                    )
+                })
+            }
+        }
+    }
+}
+
+/// Return the embedded profile with the extra syscalls for auditing
+///
+/// # Errors
+///
+/// If the extra syscalls are invalid
+pub fn dump() -> Result<String> {
+    let syscalls = extra_syscalls()?;
+    if syscalls.is_empty() {
+        Ok(EMBEDDED_PROFILE.to_owned())
+    } else {
+        merge(EMBEDDED_PROFILE, &syscalls)
+    }
+}
+
+/// The syscalls from [`envs::CARGO_GUNGRAUN_SECCOMP_SYSCALLS`] which are additionally allowed
+///
+/// The syscalls are separated by commas.
+fn extra_syscalls() -> Result<Vec<String>> {
+    let value = match std::env::var(envs::CARGO_GUNGRAUN_SECCOMP_SYSCALLS) {
+        Ok(value) => value,
+        Err(VarError::NotUnicode(_)) => {
+            return Err(anyhow!(
+                "Invalid {}: Not utf8",
+                envs::CARGO_GUNGRAUN_SECCOMP_SYSCALLS
+            ))
+        }
+        Err(VarError::NotPresent) => return Ok(vec![]),
+    };
+
+    parse_syscalls(&value)
+}
+
+/// Allow the `syscalls` in the seccomp `profile`
+///
+/// The syscalls are removed from all rules which don't allow them and then added with a new
+/// rule allowing them unconditionally.
+fn merge(profile: &str, syscalls: &[String]) -> Result<String> {
+    let mut profile = serde_json::from_str::<Value>(profile)
+        .with_context(|| "Failed to parse the seccomp profile")?;
+    let rules = profile
+        .get_mut("syscalls")
+        .and_then(Value::as_array_mut)
+        .with_context(|| "Invalid seccomp profile: Missing the 'syscalls' array")?;
+
+    for rule in rules.iter_mut() {
+        if rule.get("action").and_then(Value::as_str) == Some("SCMP_ACT_ALLOW") {
+            continue;
+        }
+        if let Some(names) = rule.get_mut("names").and_then(Value::as_array_mut) {
+            names.retain(|name| {
+                !name
+                    .as_str()
+                    .is_some_and(|name| syscalls.iter().any(|s| s == name))
+            });
+        }
+    }
+    rules.retain(|rule| {
+        rule.get("names")
+            .and_then(Value::as_array)
+            .map_or(true, |names| !names.is_empty())
+    });
+
+    rules.push(json!({
+        "names": syscalls,
+        "action": "SCMP_ACT_ALLOW",
+        "comment": format!("Allowed with {}", envs::CARGO_GUNGRAUN_SECCOMP_SYSCALLS),
+    }));
+
+    serde_json::to_string_pretty(&profile).map_err(Into::into)
+}
+
+fn parse_syscalls(value: &str) -> Result<Vec<String>> {
+    let mut syscalls = vec![];
+    for syscall in value.split(',').map(str::trim).filter(|s| !s.is_empty()) {
+        if !syscall
+            .bytes()
+            .all(|b| b.is_ascii_lowercase() || b.is_ascii_digit() || b == b'_')
+        {
+            return Err(anyhow!(
+                "Invalid {}: '{syscall}' is not a valid syscall name",
+                envs::CARGO_GUNGRAUN_SECCOMP_SYSCALLS
+            ));
+        }
+        syscalls.push(syscall.to_owned());
+    }
+
+    Ok(syscalls)
+}
+
+#[cfg(test)]
+mod tests {
+    use rstest::rstest;
+
+    use super::*;
+
+    fn allowed(profile: &Value, syscall: &str) -> Vec<String> {
+        profile["syscalls"]
+            .as_array()
+            .unwrap()
+            .iter()
+            .filter(|rule| {
+                rule["names"]
+                    .as_array()
+                    .unwrap()
+                    .iter()
+                    .any(|name| name == syscall)
+            })
+            .map(|rule| rule["action"].as_str().unwrap().to_owned())
+            .collect()
+    }
+
+    #[rstest]
+    #[case::embedded("embedded", Seccomp::Embedded)]
+    #[case::empty("", Seccomp::Embedded)]
+    #[case::default("default", Seccomp::Default)]
+    #[case::unconfined("unconfined", Seccomp::Unconfined)]
+    fn seccomp_parse(#[case] value: &str, #[case] expected: Seccomp) {
+        assert_eq!(Seccomp::parse(value).unwrap(), expected);
+    }
+
+    #[test]
+    fn seccomp_parse_when_path_does_not_exist() {
+        assert_eq!(
+            Seccomp::parse("/does/not/exist.json")
+                .unwrap_err()
+                .to_string(),
+            "Invalid CARGO_GUNGRAUN_SECCOMP: Expected one of 'embedded', 'default', 'unconfined' \
+             or the path to a seccomp profile but the file '/does/not/exist.json' was not found"
+        );
+    }
+
+    #[rstest]
+    #[case::single("bpf", &["bpf"])]
+    #[case::multiple("bpf, perf_event_open,", &["bpf", "perf_event_open"])]
+    #[case::empty("", &[])]
+    fn parse_syscalls_when_valid(#[case] value: &str, #[case] expected: &[&str]) {
+        assert_eq!(parse_syscalls(value).unwrap(), expected);
+    }
+
+    #[test]
+    fn parse_syscalls_when_invalid() {
+        assert_eq!(
+            parse_syscalls("bpf,rm -rf").unwrap_err().to_string(),
+            "Invalid CARGO_GUNGRAUN_SECCOMP_SYSCALLS: 'rm -rf' is not a valid syscall name"
+        );
+    }
+
+    #[test]
+    fn embedded_profile_is_valid_json() {
+        serde_json::from_str::<Value>(EMBEDDED_PROFILE).unwrap();
+    }
+
+    #[test]
+    fn merge_allows_denied_syscall() {
+        let before = serde_json::from_str::<Value>(EMBEDDED_PROFILE).unwrap();
+        assert_eq!(allowed(&before, "userfaultfd"), ["SCMP_ACT_ERRNO"]);
+
+        let merged = merge(EMBEDDED_PROFILE, &["userfaultfd".to_owned()]).unwrap();
+        let after = serde_json::from_str::<Value>(&merged).unwrap();
+        assert_eq!(allowed(&after, "userfaultfd"), ["SCMP_ACT_ALLOW"]);
+    }
+
+    #[test]
+    fn merge_when_no_syscalls_array() {
+        assert_eq!(
+            merge("{}", &["bpf".to_owned()]).unwrap_err().to_string(),
+            "Invalid seccomp profile: Missing the 'syscalls' array"
+        );
+    }
+}
@@ -0,0 +1,543 @@
+//! The volumes mounted into the container
+
+use std::collections::HashMap;
+use std::env::VarError;
+
+use anyhow::{anyhow, Context, Result};
+use camino::{Utf8Path, Utf8PathBuf};
+
+use super::Engine;
+use crate::envs;
+use crate::meta::parse_csv;
+
+/// The file which contains `1` if `SELinux` is enforcing
+const SELINUX_ENFORCE: &str = "/sys/fs/selinux/enforce";
+
+/// The mounts which are always mounted into the container
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Mount {
+    /// The cargo home directory
+    CargoHome,
+    /// The gungraun home directory
+    GungraunHome,
+    /// The gungraun runner from [`envs::GUNGRAUN_RUNNER`]
+    Runner,
+    /// The rustup home directory
+    RustupHome,
+    /// The target directory
+    Target,
+    /// The workspace root
+    Workspace,
+}
+
+/// The options of a bind mount like `ro` or `z`
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct MountOptions(Vec<String>);
+
+/// A volume from [`envs::CARGO_GUNGRAUN_VOLUMES`]
+///
+/// The volume is mounted into the container and shared into the qemu guest at the same path.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Volume {
+    /// The absolute path in the container and guest
+    pub container: Utf8PathBuf,
+    /// The canonicalized path on the host
+    pub host: Utf8PathBuf,
+    /// The mount options
+    pub options: MountOptions,
+}
+
+impl Mount {
+    /// All built-in mounts
+    pub const ALL: [Self; 6] = [
+        Self::CargoHome,
+        Self::GungraunHome,
+        Self::Runner,
+        Self::RustupHome,
+        Self::Target,
+        Self::Workspace,
+    ];
+
+    /// The name of the mount used in [`envs::CARGO_GUNGRAUN_MOUNT_OPTIONS`]
+    #[must_use]
+    pub const fn name(self) -> &'static str {
+        match self {
+            Self::CargoHome => "cargo_home",
+            Self::GungraunHome => "gungraun_home",
+            Self::Runner => "runner",
+            Self::RustupHome => "rustup_home",
+            Self::Target => "target",
+            Self::Workspace => "workspace",
+        }
+    }
+}
+
+impl MountOptions {
+    /// Parse the comma separated mount `options` and validate them for the `engine`
+    ///
+    /// # Errors
+    ///
+    /// If an option is unknown, not supported by the engine or conflicts with another option
+    pub fn parse(options: &str, engine: Engine) -> Result<Self> {
+        let mut parsed: Vec<String> = vec![];
+        for option in options.split(',').map(str::trim).filter(|o| !o.is_empty()) {
+            match option {
+                "ro" | "rw" | "z" | "Z" => {}
+                "U" | "exec" | "noexec" if engine.is_podman() => {}
+                "U" => return Err(anyhow!("The mount option 'U' is only supported by podman")),
+                _ => return Err(anyhow!("Invalid mount option: '{option}'")),
+            }
+
+            for (first, second) in [("ro", "rw"), ("z", "Z"), ("exec", "noexec")] {
+                if (option == first && parsed.iter().any(|o| o == second))
+                    || (option == second && parsed.iter().any(|o| o == first))
+                {
+                    return Err(anyhow!(
+                        "The mount options '{first}' and '{second}' are mutually exclusive"
+                    ));
+                }
+            }
+
+            if !parsed.iter().any(|o| o == option) {
+                parsed.push(option.to_owned());
+            }
+        }
+
+        Ok(Self(parsed))
+    }
+
+    /// Add the shared relabel option `z` if `selinux` is enforcing
+    ///
+    /// The options are not changed if they already contain a relabel option.
+    #[must_use]
+    pub fn with_selinux(mut self, selinux: bool) -> Self {
+        if selinux && !self.0.iter().any(|o| o == "z" || o == "Z") {
+            self.0.push("z".to_owned());
+        }
+        self
+    }
+
+    /// Return true if the mount is read-only
+    #[must_use]
+    pub fn is_readonly(&self) -> bool {
+        self.0.iter().any(|o| o == "ro")
+    }
+
+    /// Return the volume argument `host:container[:options]`
+    #[must_use]
+    pub fn volume(&self, host: &str, container: &str) -> String {
+        if self.0.is_empty() {
+            format!("{host}:{container}")
+        } else {
+            format!("{host}:{container}:{}", self.0.join(","))
+        }
+    }
+}
+
+impl Volume {
+    /// Parse a volume in the form `HOST[:CONTAINER[:OPTIONS]]`
+    ///
+    /// A relative `HOST` path is resolved against the `workspace_root`. Without `CONTAINER`,
+    /// the volume is mounted at the same path as on the host. Container paths must not
+    /// collide with any of the `reserved` paths of the built-in mounts.
+    ///
+    /// # Errors
+    ///
+    /// If the volume is invalid or the host path doesn't exist
+    pub fn parse(
+        value: &str,
+        workspace_root: &Utf8Path,
+        reserved: &[&Utf8Path],
+        engine: Engine,
+    ) -> Result<Self> {
+        let mut parts = value.splitn(3, ':');
+        let host = parts
+            .next()
+            .filter(|h| !h.is_empty())
+            .with_context(|| format!("Missing the host path in volume '{value}'"))?;
+        let host = workspace_root
+            .join(host)
+            .canonicalize_utf8()
+            .with_context(|| format!("The host path of volume '{value}' does not exist"))?;
+
+        let container = match parts.next().filter(|c| !c.is_empty()) {
+            Some(container) => Utf8PathBuf::from(container),
+            None => host.clone(),
+        };
+        // The paths are passed to the kernel command-line of the guest
+        if !container.is_absolute()
+            || container
+                .as_str()
+                .contains(|c: char| c.is_whitespace() || c == ',')
+        {
+            return Err(anyhow!(
+                "The container path of volume '{value}' must be absolute and must not contain \
+                 whitespace or commas"
+            ));
+        }
+        if let Some(path) = reserved
+            .iter()
+            .find(|path| container.starts_with(path) || path.starts_with(&container))
+        {
+            return Err(anyhow!(
+                "The container path of volume '{value}' collides with the built-in mount '{path}'"
+            ));
+        }
+
+        let options = MountOptions::parse(parts.next().unwrap_or_default(), engine)
+            .with_context(|| format!("Invalid options of volume '{value}'"))?;
+
+        Ok(Self {
+            container,
+            host,
+            options,
+        })
+    }
+
+    /// Return the volume argument for the container engine
+    #[must_use]
+    pub fn to_arg(&self, selinux: bool) -> String {
+        self.options
+            .clone()
+            .with_selinux(selinux)
+            .volume(self.host.as_str(), self.container.as_str())
+    }
+}
+
+/// Return true if `SELinux` is enforcing on the host
+#[must_use]
+pub fn is_selinux_enforcing() -> bool {
+    std::fs::read_to_string(SELINUX_ENFORCE).is_ok_and(|enforce| enforce.trim() == "1")
+}
+
+/// The options of the built-in mounts from [`envs::CARGO_GUNGRAUN_MOUNT_OPTIONS`]
+///
+/// The format is `MOUNT=OPTIONS[;MOUNT=OPTIONS...]`, for example `cargo_home=ro,z;target=U`.
+///
+/// # Errors
+///
+/// If the environment variable is invalid
+pub fn mount_options_from_env(engine: Engine) -> Result<HashMap<Mount, MountOptions>> {
+    match std::env::var(envs::CARGO_GUNGRAUN_MOUNT_OPTIONS) {
+        Ok(value) => parse_mount_options(&value, engine)
+            .map_err(|error| anyhow!("Invalid {}: {error}", envs::CARGO_GUNGRAUN_MOUNT_OPTIONS)),
+        Err(VarError::NotUnicode(_)) => Err(anyhow!(
+            "Invalid {}: Not utf8",
+            envs::CARGO_GUNGRAUN_MOUNT_OPTIONS
+        )),
+        Err(VarError::NotPresent) => Ok(HashMap::new()),
+    }
+}
+
+fn parse_mount_options(value: &str, engine: Engine) -> Result<HashMap<Mount, MountOptions>> {
+    let mut mount_options = HashMap::new();
+    for item in value.split(';').map(str::trim).filter(|i| !i.is_empty()) {
+        let (name, options) = item
+            .split_once('=')
+            .ok_or_else(|| anyhow!("Expected 'MOUNT=OPTIONS' but found '{item}'"))?;
+        let mount = Mount::ALL
+            .into_iter()
+            .find(|mount| mount.name() == name.trim())
+            .ok_or_else(|| {
+                anyhow!(
+                    "Unknown mount '{}'. Expected one of {}",
+                    name.trim(),
+                    Mount::ALL.map(|m| format!("'{}'", m.name())).join(", ")
+                )
+            })?;
+
+        mount_options.insert(mount, MountOptions::parse(options, engine)?);
+    }
+
+    Ok(mount_options)
+}
+
+/// Parse the [`Volume`]s from [`envs::CARGO_GUNGRAUN_VOLUMES`]
+///
+/// The volumes are separated by `;` and can be quoted like csv. See [`Volume::parse`].
+///
+/// # Errors
+///
+/// If the environment variable or any of the volumes is invalid
+pub fn volumes_from_env(
+    workspace_root: &Utf8Path,
+    reserved: &[&Utf8Path],
+    engine: Engine,
+) -> Result<Vec<Volume>> {
+    let value = match std::env::var(envs::CARGO_GUNGRAUN_VOLUMES) {
+        Ok(value) => value,
+        Err(VarError::NotUnicode(_)) => {
+            return Err(anyhow!(
+                "Invalid {}: Not utf8",
+                envs::CARGO_GUNGRAUN_VOLUMES
+            ))
+        }
+        Err(VarError::NotPresent) => return Ok(vec![]),
+    };
+
+    parse_volumes(&value, workspace_root, reserved, engine)
+        .with_context(|| format!("Invalid {}", envs::CARGO_GUNGRAUN_VOLUMES))
+}
+
+fn parse_volumes(
+    value: &str,
+    workspace_root: &Utf8Path,
+    reserved: &[&Utf8Path],
+    engine: Engine,
+) -> Result<Vec<Volume>> {
+    let mut volumes: Vec<Volume> = vec![];
+    for item in parse_csv(value, b';')? {
+        let volume = Volume::parse(item.trim(), workspace_root, reserved, engine)?;
+        if let Some(other) = volumes
+            .iter()
+            .find(|v| v.container.starts_with(&volume.container))
+            .or_else(|| {
+                volumes
+                    .iter()
+                    .find(|v| volume.container.starts_with(&v.container))
+            })
+        {
+            return Err(anyhow!(
+                "The container paths '{}' and '{}' of the volumes collide",
+                other.container,
+                volume.container
+            ));
+        }
+        volumes.push(volume);
+    }
+
+    Ok(volumes)
+}
+
+#[cfg(test)]
+mod tests {
+    use rstest::rstest;
+
+    use super::*;
+
+    #[rstest]
+    #[case::empty("", Engine::Docker, &[])]
+    #[case::single("ro", Engine::Docker, &["ro"])]
+    #[case::multiple("ro, z", Engine::Docker, &["ro", "z"])]
+    #[case::duplicate("Z,Z", Engine::Docker, &["Z"])]
+    #[case::chown("U", Engine::Podman, &["U"])]
+    #[case::exec("exec,ro", Engine::PodmanRemote, &["exec", "ro"])]
+    fn mount_options_parse_when_valid(
+        #[case] options: &str,
+        #[case] engine: Engine,
+        #[case] expected: &[&str],
+    ) {
+        assert_eq!(
+            MountOptions::parse(options, engine).unwrap(),
+            MountOptions(expected.iter().map(|o| (*o).to_owned()).collect())
+        );
+    }
+
+    #[rstest]
+    #[case::unknown("foo", Engine::Podman, "Invalid mount option: 'foo'")]
+    #[case::chown_docker(
+        "U",
+        Engine::Docker,
+        "The mount option 'U' is only supported by podman"
+    )]
+    #[case::exec_docker("exec", Engine::Docker, "Invalid mount option: 'exec'")]
+    #[case::relabel(
+        "z,Z",
+        Engine::Podman,
+        "The mount options 'z' and 'Z' are mutually exclusive"
+    )]
+    #[case::readonly(
+        "rw,ro",
+        Engine::Docker,
+        "The mount options 'ro' and 'rw' are mutually exclusive"
+    )]
+    fn mount_options_parse_when_invalid(
+        #[case] options: &str,
+        #[case] engine: Engine,
+        #[case] expected: &str,
+    ) {
+        assert_eq!(
+            MountOptions::parse(options, engine)
+                .unwrap_err()
+                .to_string(),
+            expected
+        );
+    }
+
+    #[rstest]
+    #[case::disabled("ro", false, "/a:/b:ro")]
+    #[case::no_options("", true, "/a:/b:z")]
+    #[case::added("ro", true, "/a:/b:ro,z")]
+    #[case::private("Z", true, "/a:/b:Z")]
+    #[case::none("", false, "/a:/b")]
+    fn mount_options_with_selinux(
+        #[case] options: &str,
+        #[case] selinux: bool,
+        #[case] expected: &str,
+    ) {
+        let options = MountOptions::parse(options, Engine::Podman)
+            .unwrap()
+            .with_selinux(selinux);
+        assert_eq!(options.volume("/a", "/b"), expected);
+    }
+
+    #[test]
+    fn parse_mount_options_when_valid() {
+        let options = parse_mount_options("cargo_home=ro,z; target=U", Engine::Podman).unwrap();
+
+        assert_eq!(options.len(), 2);
+        assert_eq!(
+            options[&Mount::CargoHome],
+            MountOptions(vec!["ro".to_owned(), "z".to_owned()])
+        );
+        assert_eq!(options[&Mount::Target], MountOptions(vec!["U".to_owned()]));
+    }
+
+    #[rstest]
+    #[case::missing_options("cargo_home", "Expected 'MOUNT=OPTIONS' but found 'cargo_home'")]
+    #[case::unknown_mount(
+        "home=ro",
+        "Unknown mount 'home'. Expected one of 'cargo_home', 'gungraun_home', 'runner', \
+         'rustup_home', 'target', 'workspace'"
+    )]
+    fn parse_mount_options_when_invalid(#[case] value: &str, #[case] expected: &str) {
+        assert_eq!(
+            parse_mount_options(value, Engine::Podman)
+                .unwrap_err()
+                .to_string(),
+            expected
+        );
+    }
+
+    /// A temporary workspace with the directories `data` and `other`
+    fn workspace(name: &str) -> Utf8PathBuf {
+        let root = Utf8PathBuf::try_from(std::env::temp_dir())
+            .unwrap()
+            .join(format!("cargo-gungraun-test-{}-{name}", std::process::id()));
+        std::fs::create_dir_all(root.join("data")).unwrap();
+        std::fs::create_dir_all(root.join("other")).unwrap();
+        root.canonicalize_utf8().unwrap()
+    }
+
+    fn reserved() -> Vec<&'static Utf8Path> {
+        ["/workspace", "/target", "/gungraun_home"]
+            .map(Utf8Path::new)
+            .to_vec()
+    }
+
+    #[rstest]
+    #[case::relative("data:/data", "data", "/data", &[])]
+    #[case::same_path("data", "data", "", &[])]
+    #[case::options("data:/data:ro,Z", "data", "/data", &["ro", "Z"])]
+    #[case::nested_relative("other/../data:/data", "data", "/data", &[])]
+    fn volume_parse_when_valid(
+        #[case] value: &str,
+        #[case] host: &str,
+        #[case] container: &str,
+        #[case] options: &[&str],
+    ) {
+        let root = workspace("valid");
+        let volume = Volume::parse(value, &root, &reserved(), Engine::Podman).unwrap();
+
+        assert_eq!(volume.host, root.join(host));
+        if container.is_empty() {
+            assert_eq!(volume.container, root.join(host));
+        } else {
+            assert_eq!(volume.container, container);
+        }
+        assert_eq!(
+            volume.options,
+            MountOptions(options.iter().map(|o| (*o).to_owned()).collect())
+        );
+    }
+
+    #[rstest]
+    #[case::missing_host(":/data", "Missing the host path in volume ':/data'")]
+    #[case::not_exists(
+        "missing:/data",
+        "The host path of volume 'missing:/data' does not exist"
+    )]
+    #[case::relative_container(
+        "data:data",
+        "The container path of volume 'data:data' must be absolute and must not contain whitespace \
+         or commas"
+    )]
+    #[case::whitespace(
+        "data:/my data",
+        "The container path of volume 'data:/my data' must be absolute and must not contain \
+         whitespace or commas"
+    )]
+    #[case::reserved(
+        "data:/workspace",
+        "The container path of volume 'data:/workspace' collides with the built-in mount \
+         '/workspace'"
+    )]
+    #[case::reserved_nested(
+        "data:/target/data",
+        "The container path of volume 'data:/target/data' collides with the built-in mount \
+         '/target'"
+    )]
+    #[case::reserved_parent(
+        "data:/",
+        "The container path of volume 'data:/' collides with the built-in mount '/workspace'"
+    )]
+    #[case::options("data:/data:foo", "Invalid options of volume 'data:/data:foo'")]
+    fn volume_parse_when_invalid(#[case] value: &str, #[case] expected: &str) {
+        let root = workspace("invalid");
+        assert_eq!(
+            Volume::parse(value, &root, &reserved(), Engine::Podman)
+                .unwrap_err()
+                .to_string(),
+            expected
+        );
+    }
+
+    #[rstest]
+    #[case::single("data:/data", vec!["data:/data"])]
+    #[case::trailing_semicolon("data:/data;", vec!["data:/data"])]
+    #[case::multiple("data:/data;;other:/other", vec!["data:/data", "other:/other"])]
+    #[case::comma("data,more:/data;other:/other", vec!["data,more:/data", "other:/other"])]
+    #[case::quote_semicolons("\"da;ta:/data\";other:/other", vec!["da;ta:/data", "other:/other"])]
+    fn parse_csv_when_semicolon(#[case] data: &str, #[case] expected: Vec<&str>) {
+        assert_eq!(parse_csv(data, b';').unwrap(), expected);
+    }
+
+    #[test]
+    fn parse_volumes_when_valid() {
+        let root = workspace("volumes");
+        let volumes = parse_volumes(
+            "data:/data;\"other:/other:ro\"",
+            &root,
+            &reserved(),
+            Engine::Docker,
+        )
+        .unwrap();
+
+        assert_eq!(
+            volumes
+                .iter()
+                .map(|v| v.to_arg(true))
+                .collect::<Vec<String>>(),
+            [
+                format!("{root}/data:/data:z"),
+                format!("{root}/other:/other:ro,z")
+            ]
+        );
+    }
+
+    #[test]
+    fn parse_volumes_when_colliding() {
+        let root = workspace("colliding");
+        assert_eq!(
+            parse_volumes(
+                "data:/data;other:/data/other",
+                &root,
+                &reserved(),
+                Engine::Docker
+            )
+            .unwrap_err()
+            .to_string(),
+            "The container paths '/data' and '/data/other' of the volumes collide"
+        );
+    }
+}
@@ -1,13 +1,189 @@
 //! The crate error
+//!
+//! The exit status of `cargo-gungraun` depends on the category of the error:
+//!
+//! | Code | Category |
+//! | ---- | -------- |
+//! | 0    | Success |
+//! | 1    | Any other error |
+//! | 2    | Invalid command line arguments or usage |
+//! | 3    | Performance regressions detected by gungraun |
+//! | 10   | Invalid configuration in the environment or the manifest |
+//! | 11   | The container engine was not found or is not reachable |
+//! | 12   | The image is not available |
+//! | 13   | The bootstrap script of the container failed |
+//! | 14   | Booting the qemu guest failed |
+//! | 15   | Building the benches failed |
+//! | 16   | The toolchain or the standard library of the target is not available |
+//!
+//! If a bench fails, its own exit code is used unchanged. Without a target, the benches run on
+//! the host and a failing `cargo bench` exits with the exit code of cargo.
+//!
+//! The [`Error::hints`] suggest how to fix the error. They are printed after the error with
+//! the log target [`HINT_TARGET`].
 
 use core::fmt::Display;
 use std::process::ExitStatus;
 
+use crate::envs;
+
+/// The log target of the hints which is rendered as its own level
+pub const HINT_TARGET: &str = "cargo_gungraun::hint";
+
+/// The exit code of gungraun if performance regressions were detected
+pub const REGRESSION_EXIT_CODE: i32 = 3;
+
 /// The cargo-gungraun error
 #[derive(Debug)]
 pub enum Error {
+    Api {
+        status: u16,
+        message: String,
+    },
+    /// Invalid command line arguments
+    Args(String),
+    /// A bench exited with the `code` or, without the name of the `bench`, `cargo bench`
+    Benchmark {
+        bench: Option<String>,
+        code: i32,
+    },
+    /// The bootstrap script of the container with this name failed
+    Bootstrap(String),
+    /// Building the benches failed and cargo exited with this status
+    Build(ExitStatus),
     CommandSpawn(std::io::Error),
     Command(ExitStatus),
+    /// Invalid configuration in the environment or the manifest
+    Config(String),
+    /// The container engine was not found
+    EngineMissing(String),
+    /// The container engine is not running or not reachable
+    EngineUnreachable(String),
+    /// The image is not available
+    ImageMissing(String),
+    /// The current directory is not within the workspace root
+    OutsideWorkspace {
+        current_dir: String,
+        workspace_root: String,
+    },
+    /// Gungraun detected performance regressions in the bench
+    Regression(String),
+    /// The toolchain or the standard library of the target is not available
+    Toolchain(String),
+    /// Booting the qemu guest failed
+    VmBoot,
+}
+
+impl Error {
+    /// The exit code of `cargo-gungraun` for this error as documented in the [module](self)
+    #[must_use]
+    pub const fn exit_code(&self) -> i32 {
+        match self {
+            Self::Api { .. } | Self::CommandSpawn(_) | Self::Command(_) => 1,
+            Self::Args(_) | Self::OutsideWorkspace { .. } => 2,
+            Self::Benchmark { code, .. } => *code,
+            Self::Bootstrap(_) => 13,
+            Self::Build(_) => 15,
+            Self::Config(_) => 10,
+            Self::EngineMissing(_) | Self::EngineUnreachable(_) => 11,
+            Self::ImageMissing(_) => 12,
+            Self::Regression(_) => REGRESSION_EXIT_CODE,
+            Self::Toolchain(_) => 16,
+            Self::VmBoot => 14,
+        }
+    }
+
+    /// The exit code for the `error` which is the code of the outermost [`Error`] or else `1`
+    #[must_use]
+    pub fn exit_code_of(error: &anyhow::Error) -> i32 {
+        error.downcast_ref::<Self>().map_or(1, Self::exit_code)
+    }
+
+    /// The suggestions how to fix this error
+    #[must_use]
+    pub fn hints(&self) -> Vec<String> {
+        let doctor = "Run `cargo gungraun doctor` to check the setup".to_owned();
+        match self {
+            Self::Api { .. } => vec![
+                format!(
+                    "Set {}=cli to use the command line interface of the container engine",
+                    envs::CARGO_GUNGRAUN_BACKEND
+                ),
+                doctor,
+            ],
+            Self::Args(_) => vec!["Run `cargo gungraun help` to see the usage".to_owned()],
+            Self::Benchmark { .. } => vec![format!(
+                "Run with {}=debug to see more details",
+                envs::GUNGRAUN_LOG
+            )],
+            Self::Bootstrap(_) => vec![
+                format!(
+                    "Run with {}=debug to see the output of the bootstrap script",
+                    envs::GUNGRAUN_LOG
+                ),
+                doctor,
+            ],
+            Self::Build(_) => vec![
+                "Fix the build errors above. With a target, the benches are built in the container \
+                 for the target"
+                    .to_owned(),
+            ],
+            Self::CommandSpawn(_) | Self::Command(_) | Self::Regression(_) => vec![],
+            Self::Config(_) => vec!["Check the CARGO_GUNGRAUN_* environment variables, the env \
+                                     file and the [workspace.metadata.gungraun] table of the \
+                                     workspace manifest"
+                .to_owned()],
+            Self::EngineMissing(_) => vec![
+                "Install podman (recommended) or docker".to_owned(),
+                format!(
+                    "Set {} to select another engine or {} to the path of its executable",
+                    envs::CARGO_GUNGRAUN_ENGINE,
+                    envs::CARGO_GUNGRAUN_ENGINE_PATH
+                ),
+                doctor,
+            ],
+            Self::EngineUnreachable(_) => vec![
+                "Start the container engine, for example with `systemctl start docker` or `podman \
+                 machine start`"
+                    .to_owned(),
+                "Check that the current user is allowed to use the container engine".to_owned(),
+            ],
+            Self::ImageMissing(_) => vec![
+                "Run without `--offline` to pull the image".to_owned(),
+                format!("Set {} to use another image", envs::CARGO_GUNGRAUN_IMAGE),
+            ],
+            Self::OutsideWorkspace { workspace_root, .. } => vec![format!(
+                "Run cargo-gungraun from inside the workspace '{workspace_root}' or use \
+                 `--manifest-path`"
+            )],
+            Self::Toolchain(_) => vec![
+                "Install rustup from https://rustup.rs to manage the toolchain and the standard \
+                 library of the target"
+                    .to_owned(),
+                format!(
+                    "Set {} to the sysroot of a toolchain with `bin/cargo`, `bin/rustc` and the \
+                     standard library of the target in `lib/rustlib/<target>`",
+                    envs::CARGO_GUNGRAUN_SYSROOT
+                ),
+            ],
+            Self::VmBoot => vec![
+                format!(
+                    "Increase {} if booting the qemu guest is slow",
+                    envs::CARGO_GUNGRAUN_QEMU_TIMEOUT
+                ),
+                "Run `cargo gungraun shell` to investigate the container".to_owned(),
+                doctor,
+            ],
+        }
+    }
+
+    /// The [`Error::hints`] of the outermost [`Error`] of the `error`
+    #[must_use]
+    pub fn hints_of(error: &anyhow::Error) -> Vec<String> {
+        error
+            .downcast_ref::<Self>()
+            .map_or_else(Vec::new, Self::hints)
+    }
 }
 
 impl std::error::Error for Error {}
@@ -16,11 +192,113 @@ impl Display for Error {
     fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
         // TODO: Improve error messages
         match self {
+            Self::Api { status, message } => write!(
+                f,
+                "The container engine api returned status {status}: {message}"
+            ),
+            Self::Args(message)
+            | Self::Config(message)
+            | Self::EngineMissing(message)
+            | Self::EngineUnreachable(message)
+            | Self::ImageMissing(message)
+            | Self::Toolchain(message) => write!(f, "{message}"),
+            Self::Benchmark {
+                bench: Some(bench),
+                code,
+            } => write!(f, "The bench '{bench}' failed with exit code {code}"),
+            Self::Benchmark { bench: None, code } => {
+                write!(f, "The benchmarks failed with exit code {code}")
+            }
+            Self::Bootstrap(name) => {
+                write!(f, "The bootstrap script of the container '{name}' failed")
+            }
+            Self::Build(exit_status) => write!(
+                f,
+                "Failed to build the benches: Exit status of cargo was: {exit_status}"
+            ),
             Self::CommandSpawn(error) => write!(f, "Failed spawning command: {error}"),
             Self::Command(exit_status) => write!(
                 f,
                 "Failed executing command: Exit status was: {exit_status}"
             ),
+            Self::OutsideWorkspace {
+                current_dir,
+                workspace_root,
+            } => write!(
+                f,
+                "The current directory '{current_dir}' is not within the workspace root \
+                 '{workspace_root}'"
+            ),
+            Self::Regression(bench) => {
+                write!(f, "Performance regressions detected in the bench '{bench}'")
+            }
+            Self::VmBoot => write!(f, "Failed to boot the qemu guest"),
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use std::os::unix::process::ExitStatusExt;
+
+    use anyhow::{anyhow, Context};
+    use rstest::rstest;
+
+    use super::*;
+
+    #[rstest]
+    #[case::args(Error::Args("invalid".to_owned()), 2)]
+    #[case::benchmark(Error::Benchmark { bench: Some("bench".to_owned()), code: 42 }, 42)]
+    #[case::bootstrap(Error::Bootstrap("name".to_owned()), 13)]
+    #[case::build(Error::Build(ExitStatus::from_raw(101 << 8)), 15)]
+    #[case::command(Error::Command(ExitStatus::from_raw(2 << 8)), 1)]
+    #[case::config(Error::Config("invalid".to_owned()), 10)]
+    #[case::engine_missing(Error::EngineMissing("missing".to_owned()), 11)]
+    #[case::engine_unreachable(Error::EngineUnreachable("unreachable".to_owned()), 11)]
+    #[case::image_missing(Error::ImageMissing("missing".to_owned()), 12)]
+    #[case::outside_workspace(
+        Error::OutsideWorkspace { current_dir: "/".to_owned(), workspace_root: "/ws".to_owned() },
+        2
+    )]
+    #[case::regression(Error::Regression("bench".to_owned()), 3)]
+    #[case::toolchain(Error::Toolchain("missing".to_owned()), 16)]
+    #[case::vm_boot(Error::VmBoot, 14)]
+    fn exit_code(#[case] error: Error, #[case] expected: i32) {
+        assert_eq!(error.exit_code(), expected);
+    }
+
+    #[test]
+    fn exit_code_of_with_context() {
+        let error = Err::<(), _>(Error::Command(ExitStatus::from_raw(1 << 8)))
+            .context(Error::VmBoot)
+            .with_context(|| "Failed to open a shell")
+            .unwrap_err();
+        assert_eq!(Error::exit_code_of(&error), 14);
+    }
+
+    #[test]
+    fn exit_code_of_without_error() {
+        assert_eq!(Error::exit_code_of(&anyhow!("other")), 1);
+    }
+
+    #[test]
+    fn hints_of_with_context() {
+        let error = Err::<(), _>(Error::EngineMissing("missing".to_owned()))
+            .with_context(|| "Failed to start the container")
+            .unwrap_err();
+        assert_eq!(
+            Error::hints_of(&error),
+            [
+                "Install podman (recommended) or docker",
+                "Set CARGO_GUNGRAUN_ENGINE to select another engine or CARGO_GUNGRAUN_ENGINE_PATH \
+                 to the path of its executable",
+                "Run `cargo gungraun doctor` to check the setup"
+            ]
+        );
+    }
+
+    #[test]
+    fn hints_of_without_error() {
+        assert!(Error::hints_of(&anyhow!("other")).is_empty());
+    }
+}
@@ -5,6 +5,7 @@
 #![allow(missing_docs)]
 
 pub mod args;
+pub mod compat;
 pub mod container;
 pub mod error;
 pub mod meta;
@@ -15,21 +16,71 @@ pub mod envs {
     pub const CARGO_BUILD_TARGET: &str = "CARGO_BUILD_TARGET";
     /// Location of where to place all generated artifacts
     pub const CARGO_BUILD_TARGET_DIR: &str = "CARGO_BUILD_TARGET_DIR";
+    /// The `AppArmor` profile of the container
+    pub const CARGO_GUNGRAUN_APPARMOR: &str = "CARGO_GUNGRAUN_APPARMOR";
+    /// How to talk to the container engine: `cli` (the default) or `api`
+    pub const CARGO_GUNGRAUN_BACKEND: &str = "CARGO_GUNGRAUN_BACKEND";
+    /// The bench targets of the workspace with their harness as space separated
+    /// `BENCH=HARNESS`
+    pub const CARGO_GUNGRAUN_BENCHES: &str = "CARGO_GUNGRAUN_BENCHES";
+    /// The environment variables forwarded only into the container
+    pub const CARGO_GUNGRAUN_CONTAINER_ENVS: &str = "CARGO_GUNGRAUN_CONTAINER_ENVS";
     /// The name of the package
     pub const CARGO_GUNGRAUN_ENGINE: &str = "CARGO_GUNGRAUN_ENGINE";
-    /// The name of the package
+    /// The path to the executable of the container engine
+    pub const CARGO_GUNGRAUN_ENGINE_PATH: &str = "CARGO_GUNGRAUN_ENGINE_PATH";
+    /// The path to the unix socket of the container engine api
+    pub const CARGO_GUNGRAUN_ENGINE_SOCKET: &str = "CARGO_GUNGRAUN_ENGINE_SOCKET";
+    /// The default dotenv file whose variables are forwarded into the container and the guest
+    pub const CARGO_GUNGRAUN_ENV_FILE: &str = "CARGO_GUNGRAUN_ENV_FILE";
+    /// The environment variables forwarded into the container and the guest
     pub const CARGO_GUNGRAUN_ENVS: &str = "CARGO_GUNGRAUN_ENVS";
+    /// The path to the env file which is sourced in the qemu guest
+    pub const CARGO_GUNGRAUN_GUEST_ENV_FILE: &str = "CARGO_GUNGRAUN_GUEST_ENV_FILE";
+    /// The environment variables forwarded only into the qemu guest
+    pub const CARGO_GUNGRAUN_GUEST_ENVS: &str = "CARGO_GUNGRAUN_GUEST_ENVS";
+    /// The group id of the container user
+    pub const CARGO_GUNGRAUN_GID: &str = "CARGO_GUNGRAUN_GID";
     /// The name of the package
     pub const CARGO_GUNGRAUN_IMAGE: &str = "CARGO_GUNGRAUN_IMAGE";
+    /// The options of the built-in mounts, for example `cargo_home=ro,z;target=U`
+    pub const CARGO_GUNGRAUN_MOUNT_OPTIONS: &str = "CARGO_GUNGRAUN_MOUNT_OPTIONS";
+    /// What to do with benches which don't use gungraun: `skip`, `run-qemu-user`, `run-vm` or
+    /// `error`
+    pub const CARGO_GUNGRAUN_NON_GUNGRAUN: &str = "CARGO_GUNGRAUN_NON_GUNGRAUN";
     /// TODO: DOCS
     pub const CARGO_GUNGRAUN_QEMU_ACCELERATOR: &str = "CARGO_GUNGRAUN_QEMU_ACCELERATOR";
     /// TODO: DOCS
     pub const CARGO_GUNGRAUN_QEMU_EXTRA_ARGS: &str = "CARGO_GUNGRAUN_QEMU_EXTRA_ARGS";
+    /// The volumes shared into the qemu guest as space separated `TAG:PATH[:ro]`
+    pub const CARGO_GUNGRAUN_QEMU_MOUNTS: &str = "CARGO_GUNGRAUN_QEMU_MOUNTS";
     /// TODO: DOCS
     pub const CARGO_GUNGRAUN_QEMU_TIMEOUT: &str = "CARGO_GUNGRAUN_QEMU_TIMEOUT";
+    /// The directory of the gungraun source in the container the runner is built from
+    pub const CARGO_GUNGRAUN_RUNNER_SOURCE: &str = "CARGO_GUNGRAUN_RUNNER_SOURCE";
+    /// The runners of benches with another gungraun version as space separated `BENCH=PATH`
+    pub const CARGO_GUNGRAUN_RUNNERS: &str = "CARGO_GUNGRAUN_RUNNERS";
+    /// The expected sha256 checksum of the downloaded gungraun-runner archive
+    ///
+    /// A plain checksum only applies to the runner of the primary gungraun version. Comma
+    /// separated `VERSION=SHA256` entries apply to the runners of these versions.
+    pub const CARGO_GUNGRAUN_RUNNER_SHA256: &str = "CARGO_GUNGRAUN_RUNNER_SHA256";
+    /// Additional comma separated patterns of variables whose values are masked in the logs
+    pub const CARGO_GUNGRAUN_SECRET_ENVS: &str = "CARGO_GUNGRAUN_SECRET_ENVS";
+    /// The seccomp profile: `embedded` (the default), `default`, `unconfined` or a path
+    pub const CARGO_GUNGRAUN_SECCOMP: &str = "CARGO_GUNGRAUN_SECCOMP";
+    /// Comma separated syscalls which are additionally allowed by the seccomp profile
+    pub const CARGO_GUNGRAUN_SECCOMP_SYSCALLS: &str = "CARGO_GUNGRAUN_SECCOMP_SYSCALLS";
+    /// The file in which the runner records how each bench was run
+    pub const CARGO_GUNGRAUN_SUMMARY_FILE: &str = "CARGO_GUNGRAUN_SUMMARY_FILE";
+    /// The sysroot of a toolchain not managed by rustup with `bin/cargo`, `bin/rustc` and the
+    /// standard library of the target
+    pub const CARGO_GUNGRAUN_SYSROOT: &str = "CARGO_GUNGRAUN_SYSROOT";
     /// TODO: DOCS
     pub const CARGO_GUNGRAUN_TARGET: &str = "CARGO_GUNGRAUN_TARGET";
-    /// TODO: DOCS
+    /// The user id of the container user
+    pub const CARGO_GUNGRAUN_UID: &str = "CARGO_GUNGRAUN_UID";
+    /// Additional volumes separated by `;` in the form `HOST[:CONTAINER[:OPTIONS]]`
     pub const CARGO_GUNGRAUN_VOLUMES: &str = "CARGO_GUNGRAUN_VOLUMES";
     /// The name of the package
     pub const CARGO_HOME: &str = "CARGO_HOME";
@@ -54,10 +105,14 @@ pub mod envs {
     pub const GUNGRAUN_SEPARATE_TARGETS: &str = "GUNGRAUN_SEPARATE_TARGETS";
     /// The gungraun version used in the manifest of the target package
     pub const GUNGRAUN_VERSION: &str = "GUNGRAUN_VERSION";
+    /// The user namespace mode of podman which is respected if set
+    pub const PODMAN_USERNS: &str = "PODMAN_USERNS";
     /// TODO: DOCS
     pub const QEMU_LD_PREFIX: &str = "QEMU_LD_PREFIX";
     /// The rustup home
     pub const RUSTUP_HOME: &str = "RUSTUP_HOME";
+    /// The toolchain override of rustup
+    pub const RUSTUP_TOOLCHAIN: &str = "RUSTUP_TOOLCHAIN";
 }
 
 use core::fmt::Display;
@@ -197,6 +252,52 @@ impl Display for Target {
     }
 }
 
+/// Run `cargo bench` with the cargo arguments of the `args` on the host
+///
+/// The benches are built with `--no-run` first, so a failed build exits with the code of
+/// [`Error::Build`] and a failed bench with the exit code of cargo.
+fn bench_on_host(args: &Args) -> Result<()> {
+    let cargo_bench = |no_run: bool| {
+        // Only the rustup proxy understands the `+toolchain`
+        let mut command = match &args.toolchain {
+            Some(toolchain) => {
+                let mut command = std::process::Command::new("cargo");
+                command.arg(format!("+{toolchain}"));
+                command
+            }
+            None => std::process::Command::new(cargo_bin()),
+        };
+        command.arg("bench");
+        if no_run {
+            command.arg("--no-run");
+        }
+        if let Some(path) = &args.manifest_path {
+            command.arg("--manifest-path").arg(path);
+        }
+        command
+            .args(&args.cargo)
+            .status()
+            .map_err(Error::CommandSpawn)
+            .with_context(|| "Failed to execute cargo")
+    };
+
+    let status = cargo_bench(true)?;
+    if !status.success() {
+        return Err(Error::Build(status).into());
+    }
+
+    let status = cargo_bench(false)?;
+    if status.success() {
+        Ok(())
+    } else {
+        Err(Error::Benchmark {
+            bench: None,
+            code: status.code().unwrap_or(1),
+        }
+        .into())
+    }
+}
+
 #[must_use]
 pub fn cargo_bin() -> PathBuf {
     std::env::var_os("CARGO")
@@ -204,37 +305,76 @@ pub fn cargo_bin() -> PathBuf {
         .into()
 }
 
-/// TODO: Result error
+fn require_target(target: Option<Target>, command: &str) -> Result<Target> {
+    target.ok_or_else(|| {
+        Error::Args(format!(
+            "The `{command}` command requires a target. Use `--target` or set {}",
+            envs::CARGO_BUILD_TARGET
+        ))
+        .into()
+    })
+}
+
+/// Run cargo-gungraun with the command line arguments
 ///
 /// # Errors
-pub fn run(color: Option<Color>) -> Result<()> {
-    let args = args::parse(color)?;
+///
+/// Any error. Use [`Error::exit_code_of`] for the exit code
+pub fn run<I>(args: I, color: Option<Color>) -> Result<()>
+where
+    I: IntoIterator<Item = OsString>,
+{
+    let args = args::parse(args, color).map_err(|error| Error::Args(format!("{error:#}")))?;
+    let options = container::Options {
+        env_file: args.env_file.clone(),
+        keep: args.keep,
+        manifest_path: args.manifest_path.clone(),
+        non_gungraun: args.non_gungraun,
+        offline: args.offline,
+        selection: args.selection.clone(),
+        toolchain: args.toolchain.clone(),
+    };
     match args.command {
         args::Command::Bench if args.help => {
             args.print_bench_help()?;
         }
         args::Command::Bench => {
             if let Some(target) = args.target {
-                container::run_bench(target, args.cargo)?;
+                container::run_bench(target, args.cargo, &options)?;
             } else {
                 info!("No target given. Falling back to run `cargo bench` on the host");
-                return std::process::Command::new(cargo_bin())
-                    .args(args.cargo)
-                    .status()
-                    .map_err(Error::CommandSpawn)
-                    .and_then(|status| {
-                        if status.success() {
-                            Ok(())
-                        } else {
-                            Err(Error::Command(status))
-                        }
-                    })
-                    .with_context(|| "Failed to execute cargo");
+                return bench_on_host(&args);
             }
         }
+        args::Command::Clean { images } => {
+            container::clean(images, &options)?;
+        }
+        args::Command::Doctor => {
+            container::doctor(args.target, &options)?;
+        }
+        args::Command::Down => {
+            container::down(require_target(args.target, "down")?, &options)?;
+        }
+        args::Command::Exec { argv } => {
+            if argv.is_empty() {
+                return Err(
+                    Error::Args("The `exec` command requires a command to run".to_owned()).into(),
+                );
+            }
+            container::exec(require_target(args.target, "exec")?, &argv, &options)?;
+        }
         args::Command::Help => {
             args.print_command_help();
         }
+        args::Command::Seccomp => {
+            print!("{}", container::seccomp::dump()?);
+        }
+        args::Command::Shell { guest } => {
+            container::shell(require_target(args.target, "shell")?, guest, &options)?;
+        }
+        args::Command::Up => {
+            container::up(require_target(args.target, "up")?, &options)?;
+        }
         args::Command::Version => {
             Args::print_version();
         }
@@ -4,6 +4,7 @@ use std::io::Write;
 
 use cargo_gungraun::args::Color;
 use cargo_gungraun::envs;
+use cargo_gungraun::error::{Error, HINT_TARGET};
 use colored::{control, Colorize};
 use env_logger::Env;
 use log::error;
@@ -44,6 +45,7 @@ fn main() {
                 .module_path()
                 .unwrap_or_else(|| record.module_path_static().unwrap_or("???")),
             match record.level() {
+                _ if record.target() == HINT_TARGET => "Hint".magenta().bold(),
                 log::Level::Error => "Error".red().bold(),
                 log::Level::Warn => "Warn".yellow().bold(),
                 log::Level::Info => "Info".green().bold(),
@@ -55,8 +57,14 @@ fn main() {
     })
     .init();
 
-    if let Err(error) = cargo_gungraun::run(color.and_then(|c| Color::parse(Some(&c)).ok())) {
+    if let Err(error) = cargo_gungraun::run(
+        std::env::args_os(),
+        color.and_then(|c| Color::parse(Some(&c)).ok()),
+    ) {
         error!("{error}");
-        std::process::exit(1)
+        for hint in Error::hints_of(&error) {
+            error!(target: HINT_TARGET, "{hint}");
+        }
+        std::process::exit(Error::exit_code_of(&error))
     }
 }
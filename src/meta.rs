@@ -1,34 +1,79 @@
+use core::fmt::Display;
+use core::str::FromStr;
+use std::collections::{BTreeMap, HashMap};
 use std::env::{current_dir, VarError};
 use std::path::PathBuf;
 
 use anyhow::{anyhow, Context, Result};
-use camino::Utf8PathBuf;
+use camino::{Utf8Path, Utf8PathBuf};
 use csv::StringRecord;
 use home::{cargo_home, rustup_home};
+use log::debug;
 use rand::Rng;
 use serde::{Deserialize, Serialize};
 use simplematch::{DoWild, Options};
 
-use crate::container::Engine;
+use crate::container::env_selection::EnvSelection;
+use crate::container::runner::{ExtraRunner, RunnerSource};
+use crate::container::seccomp::Seccomp;
+use crate::container::volume::{self, Mount, MountOptions, Volume};
+use crate::container::{dotenv, CommandExt, Engine};
 use crate::error::Error;
-use crate::{cargo_bin, envs, Target};
+use crate::{cargo_bin, compat, envs, Target};
 
 pub const CARGO_GUNGRAUN_VERSION: &str = env!("CARGO_PKG_VERSION");
+/// The prefix of the name of all containers created by cargo-gungraun
+pub const CONTAINER_NAME_PREFIX: &str = "cargo-gungraun-";
+/// The repository of the default cargo-gungraun images without the target
+pub const IMAGE_REPOSITORY: &str = "ghcr.io/cargo-gungraun";
 // TODO: CHECK IF these options are needed or if without options would suffice
 pub const SIMPLEMATCH_OPTIONS: Options<u8> = Options::new().enable_escape(true).enable_classes(true);
 
-#[derive(Debug, PartialEq, Eq, Serialize, Deserialize)]
+/// A bench target of a workspace member
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Bench<'a> {
+    /// The harness of the bench
+    pub harness: Harness,
+    /// The package of the bench
+    pub package: &'a Package,
+    /// The bench target
+    pub target: &'a PackageTarget,
+}
+
+/// The output of `cargo metadata`
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
 pub struct CargoMetadata {
+    /// The `[workspace.metadata]` table of the workspace manifest
+    #[serde(default)]
+    pub metadata: Option<serde_json::Value>,
     pub packages: Vec<Package>,
+    /// The resolved dependency graph. `None` with `--no-deps`
+    #[serde(default)]
+    pub resolve: Option<Resolve>,
     pub target_directory: PathBuf,
+    #[serde(default)]
+    pub workspace_members: Vec<String>,
     pub workspace_root: PathBuf,
 }
 
+/// The project-level configuration in the `[workspace.metadata.gungraun]` table
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields, rename_all = "kebab-case")]
+pub struct Config {
+    /// The default dotenv file relative to the workspace root
+    pub env_file: Option<PathBuf>,
+    /// What to do with benches which don't use gungraun
+    pub non_gungraun: Option<HarnessPolicy>,
+}
+
 pub struct ContainerData {
     pub cargo_home: Utf8PathBuf,
     pub current_dir: Utf8PathBuf,
+    pub gid: u32,
     pub gungraun_home: Utf8PathBuf,
     pub gungraun_runner: Utf8PathBuf,
+    /// The mount point of a git or path source of gungraun the runner is built from
+    pub gungraun_source: Utf8PathBuf,
     pub home: Utf8PathBuf,
     pub name: String,
     pub qemu_runner: Utf8PathBuf,
@@ -37,43 +82,207 @@ pub struct ContainerData {
     pub separate_targets: String,
     pub shell: Utf8PathBuf,
     pub target_dir: Utf8PathBuf,
+    pub uid: u32,
     pub user: String,
+    pub volumes: Vec<Volume>,
     pub workspace_root: Utf8PathBuf,
 }
 
 pub struct EngineData {
     pub accelerator: Option<String>,
+    pub apparmor: Option<String>,
     pub engine: Engine,
-    pub envs: Vec<(String, String)>,
+    pub envs: EnvSelection,
     pub image: String,
+    pub mount_options: HashMap<Mount, MountOptions>,
+    pub rootless: bool,
+    pub seccomp: Seccomp,
     pub seccomp_path: Utf8PathBuf,
-    pub volumes: Vec<String>,
+    pub selinux: bool,
+    pub userns: Option<String>,
 }
 
 pub struct HostData {
     pub cargo_home: Utf8PathBuf,
+    pub config: Config,
     pub current_dir: Utf8PathBuf,
+    /// The runners of benches using other gungraun versions than [`Self::gungraun_version`]
+    pub extra_runners: Vec<ExtraRunner>,
+    pub gid: u32,
     pub gungraun_home: Utf8PathBuf,
     pub gungraun_runner: Option<Utf8PathBuf>,
     pub gungraun_version: String,
+    pub metadata: CargoMetadata,
+    pub runner_source: RunnerSource,
     pub rustup_home: Utf8PathBuf,
+    /// The sysroot from [`envs::CARGO_GUNGRAUN_SYSROOT`] of a toolchain not managed by rustup
+    pub sysroot: Option<Utf8PathBuf>,
     pub target_dir: Utf8PathBuf,
+    /// The active toolchain of rustup for the [`Self::current_dir`]
+    ///
+    /// `None` with a [`Self::sysroot`] or if rustup is not installed.
+    pub toolchain: Option<String>,
+    pub uid: u32,
+    pub user: String,
     pub workspace_root: Utf8PathBuf,
 }
 
-#[derive(Debug, PartialEq, Eq, Serialize, Deserialize)]
+/// A dependency of a [`Package`]
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Dependency {
+    /// `dev`, `build` or `None` for normal dependencies
+    pub kind: Option<String>,
+    /// The name of the crate, which is not changed by renaming the dependency
+    pub name: String,
+    /// True if the dependency is only enabled by a feature
+    #[serde(default)]
+    pub optional: bool,
+    /// The name of the dependency as used in the code if renamed
+    #[serde(default)]
+    pub rename: Option<String>,
+    /// The version requirement
+    pub req: String,
+}
+
+/// The benches of the workspace using the same gungraun package
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct GungraunUse<'a> {
+    /// The names of the bench targets as in the file names of the bench executables
+    pub benches: Vec<String>,
+    /// The gungraun package
+    pub gungraun: &'a Package,
+}
+
+/// The harness of a [`Bench`]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Harness {
+    /// A criterion bench with `harness = false`
+    Criterion,
+    /// A gungraun bench with `harness = false`
+    Gungraun,
+    /// The libtest harness with `#[bench]` functions
+    Libtest,
+    /// Any other custom harness
+    Other,
+}
+
+/// What to do with benches which don't use gungraun like criterion or libtest benches
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum HarnessPolicy {
+    /// Fail before running any bench
+    Error,
+    /// Run the bench with qemu-user in the container
+    RunQemuUser,
+    /// Run the bench in the qemu guest
+    RunVm,
+    /// Don't run the bench (the default)
+    #[default]
+    Skip,
+}
+
+/// A node of the [`Resolve`] graph
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Node {
+    /// The resolved dependencies
+    #[serde(default)]
+    pub deps: Vec<NodeDep>,
+    /// The package id
+    pub id: String,
+}
+
+/// A resolved dependency of a [`Node`]
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct NodeDep {
+    /// The name of the dependency as used in the code, so maybe renamed
+    pub name: String,
+    /// The package id of the dependency
+    pub pkg: String,
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
 pub struct Package {
+    #[serde(default)]
+    pub dependencies: Vec<Dependency>,
+    /// The features with the features and dependencies they enable
+    #[serde(default)]
+    pub features: BTreeMap<String, Vec<String>>,
+    #[serde(default)]
+    pub id: String,
+    pub manifest_path: PathBuf,
+    /// The `[package.metadata]` table of the manifest
+    #[serde(default)]
+    pub metadata: Option<serde_json::Value>,
     pub name: String,
+    /// The source like `registry+https://...` or `git+https://...#rev`. `None` for path
+    /// dependencies and workspace members
+    pub source: Option<String>,
+    #[serde(default)]
+    pub targets: Vec<PackageTarget>,
     pub version: String,
 }
 
+/// A build target of a [`Package`] like a lib, bin or bench
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct PackageTarget {
+    /// False if the target has `harness = false`
+    #[serde(default = "default_harness")]
+    pub harness: bool,
+    /// The kinds like `lib` or `bench`
+    pub kind: Vec<String>,
+    /// The name of the target
+    pub name: String,
+    /// The features which need to be enabled to build the target
+    #[serde(default, rename = "required-features")]
+    pub required_features: Vec<String>,
+    /// The path to the main source file of the target
+    #[serde(default)]
+    pub src_path: PathBuf,
+}
+
+/// The `-p` and `--bench` selection of the cargo arguments
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Selection {
+    /// The values of `--bench`
+    pub benches: Vec<String>,
+    /// The values of `-p` or `--package`
+    pub packages: Vec<String>,
+}
+
+/// The resolved dependency graph of `cargo metadata`
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Resolve {
+    /// The nodes of the graph, one per package
+    pub nodes: Vec<Node>,
+}
+
 impl CargoMetadata {
-    /// TODO: DOCS
+    /// Run `cargo metadata` for the workspace of the current directory or of the
+    /// `manifest_path`
     ///
     /// # Errors
-    pub fn new() -> Result<Self> {
+    ///
+    /// If cargo failed or the output could not be parsed
+    pub fn new(offline: bool, manifest_path: Option<&Utf8Path>) -> Result<Self> {
+        let mut args = vec![];
+        if offline {
+            args.push("--offline");
+        }
+        if let Some(path) = manifest_path {
+            args.extend(["--manifest-path", path.as_str()]);
+        }
+        Self::with_args(&args)
+    }
+
+    /// Run `cargo metadata` with the additional `args`
+    ///
+    /// # Errors
+    ///
+    /// If cargo failed or the output could not be parsed
+    pub fn with_args(args: &[&str]) -> Result<Self> {
         let output = std::process::Command::new(cargo_bin())
             .args(["metadata", "--format-version=1"])
+            .args(args)
             .output()
             .map_err(Error::CommandSpawn)
             .and_then(|output| {
@@ -88,24 +297,277 @@ impl CargoMetadata {
         serde_json::from_slice(&output.stdout).map_err(Into::into)
     }
 
+    /// All bench targets of the workspace members with their [`Harness`]
+    #[must_use]
+    pub fn benches(&self) -> Vec<Bench<'_>> {
+        self.workspace_packages()
+            .flat_map(|package| {
+                package
+                    .targets
+                    .iter()
+                    .filter(|target| target.is_bench())
+                    .map(move |target| Bench {
+                        harness: package.harness(target),
+                        package,
+                        target,
+                    })
+            })
+            .collect()
+    }
+
+    /// Check that the packages and benches of the `selection` exist in the workspace
+    ///
+    /// Patterns with glob characters are left to cargo.
+    ///
+    /// # Errors
+    ///
+    /// If a package is not a workspace member or a bench doesn't exist in the selected
+    /// packages
+    pub fn check_selection(&self, selection: &Selection) -> Result<()> {
+        let is_pattern = |name: &str| name.contains(['*', '?', '[']);
+
+        let members = self
+            .workspace_packages()
+            .map(|p| p.name.as_str())
+            .collect::<Vec<_>>();
+        for package in selection.packages.iter().filter(|p| !is_pattern(p)) {
+            if !members.contains(&package.as_str()) {
+                return Err(anyhow!(
+                    "The package '{package}' is not a member of the workspace. Available packages: \
+                     {}",
+                    members.join(", ")
+                ));
+            }
+        }
+
+        let benches = self
+            .benches()
+            .into_iter()
+            .filter(|bench| {
+                selection.packages.is_empty() || selection.packages.contains(&bench.package.name)
+            })
+            .map(|bench| bench.target.name.as_str())
+            .collect::<Vec<_>>();
+        for bench in selection.benches.iter().filter(|b| !is_pattern(b)) {
+            if !benches.contains(&bench.as_str()) {
+                return Err(anyhow!(
+                    "No bench target named '{bench}'. Available benches: {}",
+                    if benches.is_empty() {
+                        "none".to_owned()
+                    } else {
+                        benches.join(", ")
+                    }
+                ));
+            }
+        }
+
+        Ok(())
+    }
+
+    /// The benches of [`Self::benches`] which are selected by the `selection`
+    ///
+    /// Without any packages or benches in the `selection`, all benches are selected.
+    #[must_use]
+    pub fn selected_benches(&self, selection: &Selection) -> Vec<Bench<'_>> {
+        let matches = |patterns: &[String], name: &str| {
+            patterns.is_empty()
+                || patterns
+                    .iter()
+                    .any(|p| p.as_str().dowild_with(name, SIMPLEMATCH_OPTIONS))
+        };
+
+        self.benches()
+            .into_iter()
+            .filter(|bench| {
+                matches(&selection.packages, &bench.package.name)
+                    && matches(&selection.benches, &bench.target.name)
+            })
+            .collect()
+    }
+
+    /// The project-level [`Config`] from the `gungraun` table of the workspace metadata
+    ///
+    /// # Errors
+    ///
+    /// If the table is invalid
+    pub fn config(&self) -> Result<Config> {
+        match self.metadata.as_ref().and_then(|m| m.get("gungraun")) {
+            Some(value) => Config::deserialize(value)
+                .with_context(|| "Invalid [workspace.metadata.gungraun] table"),
+            None => Ok(Config::default()),
+        }
+    }
+
+    /// The gungraun package used by most of the benches or the first one found
+    #[must_use]
+    pub fn gungraun_package(&self) -> Option<&Package> {
+        self.gungraun_uses()
+            .ok()
+            .and_then(|uses| uses.first().map(|u| u.gungraun))
+            .or_else(|| self.packages.iter().find(|p| p.name == "gungraun"))
+    }
+
     #[must_use]
     pub fn gungraun_version(&self) -> Option<String> {
+        self.gungraun_package().map(|p| p.version.clone())
+    }
+
+    /// Group the benches of the workspace members by the gungraun package they depend on
+    ///
+    /// Only direct dependencies of the workspace members are considered. The group with the
+    /// most benches comes first. Without the resolve graph the result is empty.
+    ///
+    /// # Errors
+    ///
+    /// If benches with the same name use different gungraun packages, so their executables
+    /// can't be told apart
+    pub fn gungraun_uses(&self) -> Result<Vec<GungraunUse<'_>>> {
+        let Some(resolve) = &self.resolve else {
+            return Ok(vec![]);
+        };
+        let package = |id: &str| self.packages.iter().find(|p| p.id == id);
+
+        let mut uses: Vec<GungraunUse<'_>> = vec![];
+        for member in self.workspace_members.iter().filter_map(|id| package(id)) {
+            let Some(gungraun) = resolve
+                .nodes
+                .iter()
+                .find(|node| node.id == member.id)
+                .into_iter()
+                .flat_map(|node| &node.deps)
+                .filter_map(|dep| package(&dep.pkg))
+                .find(|p| p.name == "gungraun")
+            else {
+                continue;
+            };
+
+            let benches = member
+                .targets
+                .iter()
+                .filter(|t| t.is_bench() && member.harness(t) == Harness::Gungraun)
+                .map(|t| t.name.replace('-', "_"));
+            if let Some(group) = uses.iter_mut().find(|u| u.gungraun.id == gungraun.id) {
+                group.benches.extend(benches);
+            } else {
+                uses.push(GungraunUse {
+                    benches: benches.collect(),
+                    gungraun,
+                });
+            }
+        }
+
+        for (index, group) in uses.iter().enumerate() {
+            for other in &uses[index + 1..] {
+                if let Some(bench) = group.benches.iter().find(|b| other.benches.contains(b)) {
+                    return Err(anyhow!(
+                        "The benches named '{bench}' use gungraun {} and gungraun {}. Rename one \
+                         of them or use the same gungraun version in all packages",
+                        group.gungraun.version,
+                        other.gungraun.version
+                    ));
+                }
+            }
+        }
+
+        uses.retain(|u| !u.benches.is_empty());
+        uses.sort_by_key(|u| core::cmp::Reverse(u.benches.len()));
+        Ok(uses)
+    }
+
+    /// The workspace members
+    pub fn workspace_packages(&self) -> impl Iterator<Item = &Package> {
         self.packages
             .iter()
-            .find_map(|p| (p.name == "gungraun").then(|| p.version.clone()))
+            .filter(|p| self.workspace_members.contains(&p.id))
+    }
+}
+
+impl Display for Harness {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        match self {
+            Self::Criterion => write!(f, "criterion"),
+            Self::Gungraun => write!(f, "gungraun"),
+            Self::Libtest => write!(f, "libtest"),
+            Self::Other => write!(f, "other"),
+        }
+    }
+}
+
+impl HarnessPolicy {
+    /// The policy from [`envs::CARGO_GUNGRAUN_NON_GUNGRAUN`] if set
+    ///
+    /// # Errors
+    ///
+    /// If the value of the environment variable is invalid
+    pub fn from_env() -> Result<Option<Self>> {
+        match std::env::var(envs::CARGO_GUNGRAUN_NON_GUNGRAUN) {
+            Ok(value) => value
+                .parse()
+                .map(Some)
+                .with_context(|| format!("Invalid {}", envs::CARGO_GUNGRAUN_NON_GUNGRAUN)),
+            Err(VarError::NotUnicode(_)) => Err(anyhow!(
+                "Invalid {}: Not utf8",
+                envs::CARGO_GUNGRAUN_NON_GUNGRAUN
+            )),
+            Err(VarError::NotPresent) => Ok(None),
+        }
+    }
+}
+
+impl Display for HarnessPolicy {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        match self {
+            Self::Error => write!(f, "error"),
+            Self::RunQemuUser => write!(f, "run-qemu-user"),
+            Self::RunVm => write!(f, "run-vm"),
+            Self::Skip => write!(f, "skip"),
+        }
+    }
+}
+
+impl FromStr for HarnessPolicy {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "error" => Ok(Self::Error),
+            "run-qemu-user" => Ok(Self::RunQemuUser),
+            "run-vm" => Ok(Self::RunVm),
+            "skip" => Ok(Self::Skip),
+            _ => Err(anyhow!(
+                "Invalid policy '{s}'. Possible values are skip, run-qemu-user, run-vm, error"
+            )),
+        }
     }
 }
 
 impl ContainerData {
     /// TODO: DOCS
     ///
+    /// The container user is the host user with the same uid and gid, so files created in the
+    /// mounted directories are owned by the host user. If the container root is already mapped
+    /// to the host user, like with rootless engines, the container user is root.
+    ///
     /// # Errors
-    pub fn new(host: &HostData) -> Result<Self> {
-        // TODO: Adjust this to the gungraun user if the user namespace is not host (podman) and
-        // whatever docker needs.
-        let (user, home) = ("root".to_owned(), Utf8PathBuf::from("/root"));
+    pub fn new(host: &HostData, engine_data: &EngineData) -> Result<Self> {
+        let userns = engine_data
+            .userns
+            .clone()
+            .or_else(|| std::env::var(envs::PODMAN_USERNS).ok());
+        let is_root_mapped =
+            is_root_mapped(engine_data.engine, engine_data.rootless, userns.as_deref());
+        let (user, uid, gid, home) = if host.uid == 0 || is_root_mapped {
+            ("root".to_owned(), 0, 0, Utf8PathBuf::from("/root"))
+        } else {
+            (
+                host.user.clone(),
+                host.uid,
+                host.gid,
+                Utf8PathBuf::from(format!("/home/{}", host.user)),
+            )
+        };
         let container_name = format!(
-            "cargo-gungraun-{}",
+            "{CONTAINER_NAME_PREFIX}{}",
             hex::encode(rand::rng().random::<[u8; 8]>())
         );
 
@@ -113,27 +575,52 @@ impl ContainerData {
         let current_dir = workspace_root.join(
             host.current_dir
                 .strip_prefix(&host.workspace_root)
-                .with_context(|| "The current directory should be within the workspace root")?,
+                .map_err(|_| Error::OutsideWorkspace {
+                    current_dir: host.current_dir.to_string(),
+                    workspace_root: host.workspace_root.to_string(),
+                })?,
         );
         let separate_targets =
             std::env::var(envs::GUNGRAUN_SEPARATE_TARGETS).unwrap_or_else(|_| "yes".to_owned());
 
-        Ok(Self {
+        let mut data = Self {
             cargo_home: home.join(".cargo"),
             current_dir,
+            gid,
             gungraun_home: Utf8PathBuf::from("/gungraun_home"),
             name: container_name,
             gungraun_runner: Utf8PathBuf::from("/usr/bin/gungraun-runner"),
+            gungraun_source: Utf8PathBuf::from("/gungraun_src"),
             qemu_runner: Utf8PathBuf::from("/qemu_runner.sh"),
             runner: Utf8PathBuf::from("/runner.sh"),
             rustup_home: home.join(".rustup"),
             separate_targets,
             shell: Utf8PathBuf::from("/bin/bash"),
             target_dir: Utf8PathBuf::from("/target"),
+            uid,
             user,
+            volumes: vec![],
             workspace_root,
             home,
-        })
+        };
+
+        data.volumes = volume::volumes_from_env(
+            &host.workspace_root,
+            &[
+                &data.cargo_home,
+                &data.gungraun_home,
+                &data.gungraun_runner,
+                &data.gungraun_source,
+                &data.qemu_runner,
+                &data.runner,
+                &data.rustup_home,
+                &data.target_dir,
+                &data.workspace_root,
+            ],
+            engine_data.engine,
+        )?;
+
+        Ok(data)
     }
 }
 
@@ -141,50 +628,53 @@ impl EngineData {
     /// TODO: DOCS
     ///
     /// # Errors
-    pub fn new(target: Target, host_data: &HostData) -> Result<Self> {
-        let engine = match std::env::var_os(envs::CARGO_GUNGRAUN_ENGINE) {
-            Some(var) => Engine::try_from(var.as_os_str())?,
-            None => Engine::Podman
-                .resolve()
-                .map_or_else(|_| Engine::Docker, |_| Engine::Podman),
-        };
+    pub fn new(target: Target, host_data: &HostData, env_file: Option<&Utf8Path>) -> Result<Self> {
+        let config = |error: anyhow::Error| Error::Config(format!("{error:#}"));
+        let engine = Engine::from_env().map_err(config)?;
 
-        let image = if let Ok(value) = std::env::var(envs::CARGO_GUNGRAUN_IMAGE) {
-            value
-        } else {
-            // TODO: Adjust this to the real address
-            format!("ghcr.io/cargo-gungraun/{target}:{CARGO_GUNGRAUN_VERSION}")
-        };
+        let image = image(target);
 
-        // TODO: canonicalize_utf8? Parse like csv but with ';' as delimiter?
-        let volumes = match std::env::var(envs::CARGO_GUNGRAUN_VOLUMES) {
-            Ok(value) => value.split(';').map(ToOwned::to_owned).collect(),
-            Err(VarError::NotUnicode(_)) => {
-                return Err(anyhow!(
-                    "Invalid {}: Not utf8",
-                    envs::CARGO_GUNGRAUN_VOLUMES
-                ))
-            }
-            Err(VarError::NotPresent) => vec![],
-        };
-
-        let envs = match std::env::var(envs::CARGO_GUNGRAUN_ENVS) {
-            Ok(value) => resolve_csv_env(&value)?,
-            Err(VarError::NotUnicode(_)) => {
-                return Err(anyhow!("Invalid {}: Not utf8", envs::CARGO_GUNGRAUN_ENVS))
-            }
-            Err(VarError::NotPresent) => vec![],
-        };
+        let default_env_file = host_data
+            .config
+            .env_file
+            .as_deref()
+            .map(|path| {
+                Utf8Path::from_path(path)
+                    .ok_or_else(|| anyhow!("The env file '{}' is not utf8", path.display()))
+            })
+            .transpose()
+            .map_err(config)?;
+        let envs = dotenv::load(env_file, default_env_file)
+            .and_then(|dotenv| EnvSelection::from_env(&dotenv))
+            .map_err(config)?;
 
         let accelerator = std::env::var(envs::CARGO_GUNGRAUN_QEMU_ACCELERATOR).ok();
 
+        let rootless = engine.is_rootless()?;
+        // Rootless podman maps the host user to the same uid and gid in the container with
+        // `keep-id`. An explicit PODMAN_USERNS is respected and taken into account by
+        // `ContainerData::new`.
+        let userns =
+            (engine.is_podman() && rootless && std::env::var_os(envs::PODMAN_USERNS).is_none())
+                .then(|| "keep-id".to_owned());
+
+        let selinux = volume::is_selinux_enforcing();
+        if selinux {
+            debug!("SELinux is enforcing. Relabeling the volumes with 'z'");
+        }
+
         Ok(Self {
             accelerator,
+            apparmor: std::env::var(envs::CARGO_GUNGRAUN_APPARMOR).ok(),
             engine,
             envs,
             image,
+            mount_options: volume::mount_options_from_env(engine).map_err(config)?,
+            rootless,
+            seccomp: Seccomp::from_env().map_err(config)?,
             seccomp_path: host_data.gungraun_home.join("seccomp.json"),
-            volumes,
+            selinux,
+            userns,
         })
     }
 
@@ -193,14 +683,39 @@ impl EngineData {
     pub const fn has_accelerator(&self) -> bool {
         self.accelerator.is_some()
     }
+
+    /// The volume argument of the built-in `mount` including the `SELinux` relabel option
+    ///
+    /// # Errors
+    ///
+    /// If the default options of the mount are not supported by the engine
+    pub fn mount_volume(&self, mount: Mount, host: &str, container: &str) -> Result<String> {
+        let options = match self.mount_options.get(&mount) {
+            Some(options) => options.clone(),
+            None if mount == Mount::Runner => {
+                MountOptions::parse(self.engine.readonly_exec_options(), self.engine)?
+            }
+            None => MountOptions::default(),
+        };
+
+        Ok(options.with_selinux(self.selinux).volume(host, container))
+    }
 }
 
 impl HostData {
-    /// TODO: DOCS
+    /// Collect the data of the host for the workspace of the current directory or, with a
+    /// `manifest_path`, of the package of this manifest
+    ///
+    /// With a `manifest_path`, the [`Self::current_dir`] is the directory of the manifest. The
+    /// `toolchain` from `+toolchain` overrides the toolchain rustup would select there.
     ///
     /// # Errors
     #[allow(clippy::too_many_lines)]
-    pub fn new() -> Result<Self> {
+    pub fn new(
+        offline: bool,
+        manifest_path: Option<&Utf8Path>,
+        toolchain: Option<&str>,
+    ) -> Result<Self> {
         let cargo_home: Utf8PathBuf = cargo_home()
             .with_context(|| "Failed resolving cargo home directory")?
             .try_into()
@@ -210,13 +725,38 @@ impl HostData {
             .try_into()
             .with_context(|| "Failed converting rustup home directory into an utf8 path")?;
 
-        let metadata = CargoMetadata::new()?;
-        let gungraun_version = metadata
-            .gungraun_version()
-            .with_context(|| "Failed to detect gungraun version. Is gungraun installed?")?;
+        let manifest_path = manifest_path
+            .map(|path| {
+                path.canonicalize_utf8().map_err(|error| {
+                    Error::Args(format!("Invalid --manifest-path '{path}': {error}"))
+                })
+            })
+            .transpose()?;
+        let metadata = CargoMetadata::new(offline, manifest_path.as_deref())?;
+        let mut config = metadata
+            .config()
+            .map_err(|error| Error::Config(format!("{error:#}")))?;
+        config.env_file = config
+            .env_file
+            .map(|path| metadata.workspace_root.join(path));
+        let gungraun_uses = metadata.gungraun_uses()?;
+        let gungraun = metadata.gungraun_package().with_context(|| {
+            if metadata.packages.iter().any(|p| p.name == "iai-callgrind") {
+                format!(
+                    "iai-callgrind is not supported. Migrate to gungraun >= {}, the successor of \
+                     iai-callgrind",
+                    compat::MIN_GUNGRAUN_VERSION
+                )
+            } else {
+                "Failed to detect gungraun version. Is gungraun installed?".to_owned()
+            }
+        })?;
+        let gungraun_version = gungraun.version.clone();
+        let runner_source = RunnerSource::from_package(gungraun)?;
 
         let mut target_dir: Utf8PathBuf = metadata
             .target_directory
+            .clone()
             .try_into()
             .with_context(|| "Failed converting target directory into an utf8 path")?;
 
@@ -229,20 +769,53 @@ impl HostData {
 
         let workspace_root: Utf8PathBuf = metadata
             .workspace_root
+            .clone()
             .try_into()
             .with_context(|| "Failed converting workspace root directory into an utf8 path")?;
 
-        let mut current_dir: Utf8PathBuf = current_dir()
+        let mut process_dir: Utf8PathBuf = current_dir()
             .with_context(|| "Failed retrieving current directory")?
             .try_into()
             .with_context(|| "Failed converting current directory into an utf8 path")?;
 
-        current_dir = current_dir
+        process_dir = process_dir
             .canonicalize_utf8()
             .with_context(|| "Failed to canonicalize the current directory")?;
 
+        // Relative paths in the environment are still relative to the directory of the process
+        let current_dir = manifest_path
+            .as_deref()
+            .and_then(Utf8Path::parent)
+            .map_or_else(|| process_dir.clone(), ToOwned::to_owned);
+        let sysroot = sysroot_from_env()?;
+        let toolchain = match (&sysroot, toolchain) {
+            (Some(_), Some(toolchain)) => {
+                return Err(Error::Args(format!(
+                    "`+{toolchain}` can't be used together with {}",
+                    envs::CARGO_GUNGRAUN_SYSROOT
+                ))
+                .into())
+            }
+            (Some(sysroot), None) => {
+                debug!("Using the toolchain in the sysroot '{sysroot}'");
+                None
+            }
+            (None, toolchain) if which::which("rustup").is_ok() => {
+                let toolchain = active_toolchain(&current_dir, toolchain)?;
+                debug!("Using the toolchain '{toolchain}'");
+                Some(toolchain)
+            }
+            (None, Some(toolchain)) => {
+                return Err(Error::Toolchain(format!(
+                    "rustup was not found but is required for `+{toolchain}`"
+                ))
+                .into())
+            }
+            (None, None) => None,
+        };
+
         let mut gungraun_home = match std::env::var(envs::GUNGRAUN_HOME) {
-            Ok(path) => current_dir.join(Utf8PathBuf::from(&path)),
+            Ok(path) => process_dir.join(Utf8PathBuf::from(&path)),
             Err(_) => target_dir.join("gungraun"),
         };
 
@@ -255,26 +828,289 @@ impl HostData {
 
         // TODO: canonicalize_utf8?
         let gungraun_runner = std::env::var_os(envs::GUNGRAUN_RUNNER)
-            .map(|var| Utf8PathBuf::try_from(var).map(|path| current_dir.join(path)))
+            .map(|var| Utf8PathBuf::try_from(var).map(|path| process_dir.join(path)))
             .transpose()
             .with_context(|| format!("{} points to an invalid utf8 path", envs::GUNGRAUN_RUNNER))?;
+        let extra_runners = extra_runners(&gungraun_uses, gungraun_runner.is_some())?;
+
+        let uid = id("-u")?
+            .parse()
+            .with_context(|| "Failed to parse the user id")?;
+        let gid = id("-g")?
+            .parse()
+            .with_context(|| "Failed to parse the group id")?;
+        let user = id("-un").unwrap_or_else(|_| "gungraun".to_owned());
 
         Ok(Self {
             cargo_home,
+            config,
             current_dir,
+            extra_runners,
+            gid,
             gungraun_home,
             gungraun_runner,
             gungraun_version,
+            metadata,
+            runner_source,
             rustup_home,
+            sysroot,
             target_dir,
+            toolchain,
+            uid,
+            user,
             workspace_root,
         })
     }
+
+    /// The path of the cached gungraun-runner in the gungraun home
+    ///
+    /// The cache directory is versioned by [`RunnerSource::cache_path`].
+    #[must_use]
+    pub fn cached_runner(&self) -> Utf8PathBuf {
+        self.gungraun_home.join(self.runner_source.cache_path())
+    }
+
+    /// The local gungraun-runner mounted into the container
+    ///
+    /// This is the runner from [`envs::GUNGRAUN_RUNNER`] or else the cached runner if it
+    /// exists.
+    #[must_use]
+    pub fn runner(&self) -> Option<Utf8PathBuf> {
+        self.gungraun_runner.clone().or_else(|| {
+            let cached = self.cached_runner();
+            cached.is_file().then_some(cached)
+        })
+    }
+
+    /// The sysroot of the toolchain which is [`Self::sysroot`] or else the sysroot of rustc of
+    /// the rustup [`Self::toolchain`]
+    ///
+    /// # Errors
+    ///
+    /// If there is neither a [`Self::sysroot`] nor rustup or if rustc failed
+    pub fn rust_sysroot(&self) -> Result<Utf8PathBuf> {
+        if let Some(sysroot) = &self.sysroot {
+            return Ok(sysroot.clone());
+        }
+        let Some(toolchain) = &self.toolchain else {
+            return Err(Error::Toolchain(format!(
+                "rustup was not found. Without rustup, {} is required",
+                envs::CARGO_GUNGRAUN_SYSROOT
+            ))
+            .into());
+        };
+
+        std::process::Command::new("rustc")
+            .args(["--print", "sysroot"])
+            .env(envs::RUSTUP_TOOLCHAIN, toolchain)
+            .run_with_output()
+            .map(|output| Utf8PathBuf::from(output.trim()))
+            .with_context(|| {
+                format!("Failed to determine the sysroot of the toolchain '{toolchain}'")
+            })
+    }
+}
+
+impl Package {
+    /// True if the package depends on the crate `name`
+    #[must_use]
+    pub fn depends_on(&self, name: &str) -> bool {
+        self.dependencies.iter().any(|d| d.name == name)
+    }
+
+    /// The [`Harness`] of the bench `target` of this package
+    ///
+    /// Benches with `harness = false` are classified by the dependencies of the package. If
+    /// the package depends on gungraun and criterion, the source of the bench decides.
+    #[must_use]
+    pub fn harness(&self, target: &PackageTarget) -> Harness {
+        if target.harness {
+            return Harness::Libtest;
+        }
+
+        match (self.depends_on("gungraun"), self.depends_on("criterion")) {
+            (true, false) => Harness::Gungraun,
+            (false, true) => Harness::Criterion,
+            (true, true) => match std::fs::read_to_string(&target.src_path) {
+                Ok(source) if source.contains("gungraun::") => Harness::Gungraun,
+                Ok(source) if source.contains("criterion::") => Harness::Criterion,
+                Ok(_) | Err(_) => Harness::Other,
+            },
+            (false, false) => Harness::Other,
+        }
+    }
+}
+
+impl PackageTarget {
+    /// True if this is a bench target
+    #[must_use]
+    pub fn is_bench(&self) -> bool {
+        self.kind.iter().any(|k| k == "bench")
+    }
+}
+
+/// Resolve the active toolchain in the `dir` like rustup does
+///
+/// The order is the `toolchain` from `+toolchain`, [`envs::RUSTUP_TOOLCHAIN`], a directory
+/// override, a `rust-toolchain.toml` or `rust-toolchain` file in the `dir` or its parents and
+/// then the default toolchain. Returns the full name of the toolchain, for example
+/// `nightly-x86_64-unknown-linux-gnu`.
+///
+/// # Errors
+///
+/// If rustup failed or printed no toolchain
+pub fn active_toolchain(dir: &Utf8Path, toolchain: Option<&str>) -> Result<String> {
+    let mut command = std::process::Command::new("rustup");
+    command.args(["show", "active-toolchain"]).current_dir(dir);
+    if let Some(toolchain) = toolchain {
+        command.env(envs::RUSTUP_TOOLCHAIN, toolchain);
+    }
+
+    let output = command
+        .run_with_output()
+        .with_context(|| "Failed to resolve the active toolchain with rustup")?;
+    parse_active_toolchain(&output)
+        .map(ToOwned::to_owned)
+        .ok_or_else(|| anyhow!("rustup printed no active toolchain: '{}'", output.trim()))
+}
+
+/// The default of [`PackageTarget::harness`] if not present
+const fn default_harness() -> bool {
+    true
+}
+
+/// The [`ExtraRunner`]s of all but the first of the gungraun `uses`
+///
+/// The additional runners are always downloaded, so only released gungraun versions are
+/// supported. An explicit runner with `has_runner_override` can't serve more than one version.
+fn extra_runners(uses: &[GungraunUse<'_>], has_runner_override: bool) -> Result<Vec<ExtraRunner>> {
+    let Some((primary, others)) = uses.split_first() else {
+        return Ok(vec![]);
+    };
+    if others.is_empty() {
+        return Ok(vec![]);
+    }
+
+    let versions = uses
+        .iter()
+        .map(|u| format!("gungraun {}", u.gungraun.version))
+        .collect::<Vec<_>>()
+        .join(", ");
+    if has_runner_override {
+        return Err(anyhow!(
+            "{} can only be used with a single gungraun version but the benches use {versions}",
+            envs::GUNGRAUN_RUNNER
+        ));
+    }
+
+    let mut runners = vec![];
+    for other in others {
+        if !matches!(
+            RunnerSource::from_package(other.gungraun)?,
+            RunnerSource::Release(_)
+        ) || !matches!(
+            RunnerSource::from_package(primary.gungraun)?,
+            RunnerSource::Release(_)
+        ) {
+            return Err(anyhow!(
+                "The benches use {versions}. Multiple gungraun versions are only supported if all \
+                 of them are released versions from a registry"
+            ));
+        }
+        debug!(
+            "The benches {} use gungraun {}",
+            other.benches.join(", "),
+            other.gungraun.version
+        );
+        runners.push(ExtraRunner {
+            benches: other.benches.clone(),
+            version: other.gungraun.version.clone(),
+        });
+    }
+
+    Ok(runners)
+}
+
+/// The image of the `target` from [`envs::CARGO_GUNGRAUN_IMAGE`] or else the default image
+#[must_use]
+pub fn image(target: Target) -> String {
+    if let Ok(value) = std::env::var(envs::CARGO_GUNGRAUN_IMAGE) {
+        value
+    } else {
+        // TODO: Adjust this to the real address
+        format!("{IMAGE_REPOSITORY}/{target}:{CARGO_GUNGRAUN_VERSION}")
+    }
+}
+
+/// Return true if the container root is mapped to the host user
+///
+/// The `userns` is the user namespace in effect for podman, either ours or the one from
+/// [`envs::PODMAN_USERNS`]. Rootless podman maps root to the host user only without a user
+/// namespace or with `host`.
+fn is_root_mapped(engine: Engine, rootless: bool, userns: Option<&str>) -> bool {
+    if engine.is_podman() {
+        rootless && matches!(userns, None | Some("" | "host"))
+    } else {
+        rootless
+    }
+}
+
+/// Run `id` with the `flag` to retrieve the user or group of the current process
+fn id(flag: &str) -> Result<String> {
+    std::process::Command::new("id")
+        .arg(flag)
+        .run_with_output()
+        .map(|output| output.trim().to_owned())
+        .with_context(|| format!("Failed to run `id {flag}`"))
+}
+
+/// The canonicalized sysroot from [`envs::CARGO_GUNGRAUN_SYSROOT`] if set and not empty
+///
+/// # Errors
+///
+/// If the sysroot doesn't exist or doesn't contain `bin/cargo` and `bin/rustc`
+fn sysroot_from_env() -> Result<Option<Utf8PathBuf>> {
+    let Some(value) = std::env::var_os(envs::CARGO_GUNGRAUN_SYSROOT).filter(|v| !v.is_empty())
+    else {
+        return Ok(None);
+    };
+
+    let invalid = |reason: String| {
+        Error::Config(format!(
+            "Invalid {}: {reason}",
+            envs::CARGO_GUNGRAUN_SYSROOT
+        ))
+    };
+    let path = Utf8PathBuf::try_from(value).map_err(|_| invalid("Not utf8".to_owned()))?;
+    let sysroot = path
+        .canonicalize_utf8()
+        .map_err(|error| invalid(format!("'{path}': {error}")))?;
+    for bin in ["bin/cargo", "bin/rustc"] {
+        if !sysroot.join(bin).is_file() {
+            return Err(invalid(format!("'{sysroot}' doesn't contain '{bin}'")).into());
+        }
+    }
+
+    Ok(Some(sysroot))
 }
 
-fn parse_csv_env(data: &str) -> Result<Vec<String>> {
+/// Parse the name of the toolchain from the output of `rustup show active-toolchain`
+///
+/// The output is the name optionally followed by the reason for the selection.
+#[must_use]
+pub fn parse_active_toolchain(output: &str) -> Option<&str> {
+    output.lines().next()?.split_whitespace().next()
+}
+
+/// Parse a single csv record with the `delimiter` and skip empty fields
+///
+/// # Errors
+///
+/// If the data is not valid csv
+pub fn parse_csv(data: &str, delimiter: u8) -> Result<Vec<String>> {
     let mut reader = csv::ReaderBuilder::new()
         .has_headers(false)
+        .delimiter(delimiter)
         .flexible(true)
         .from_reader(data.as_bytes());
 
@@ -288,33 +1124,6 @@ fn parse_csv_env(data: &str) -> Result<Vec<String>> {
         .collect::<Vec<String>>())
 }
 
-fn resolve_csv_env(key: &str) -> Result<Vec<(String, String)>> {
-    match std::env::var(key) {
-        Ok(value) => {
-            let envs = parse_csv_env(&value).with_context(|| {
-                format!("Parsing environment variable '{key}' failed: Invalid csv")
-            })?;
-
-            let mut pairs = vec![];
-            for env in envs {
-                match env.split_once('=') {
-                    Some((key, value)) => pairs.push((key.to_owned(), value.to_owned())),
-                    None => {
-                        for (key, value) in std::env::vars() {
-                            if env.as_str().dowild_with(&key, SIMPLEMATCH_OPTIONS) {
-                                pairs.push((key, value));
-                            }
-                        }
-                    }
-                }
-            }
-            Ok(pairs)
-        }
-        Err(VarError::NotUnicode(_)) => Err(anyhow!("Invalid {key}: Not utf8")),
-        Err(VarError::NotPresent) => Ok(vec![]),
-    }
-}
-
 #[cfg(test)]
 mod tests {
     use rstest::rstest;
@@ -333,7 +1142,370 @@ mod tests {
     #[case::quote_spaces("\"FOO=value with spaces\",BAR=other", vec!["FOO=value with spaces", "BAR=other"])]
     #[case::quote_commas("\"FOO=value,with,commas\",BAR=other", vec!["FOO=value,with,commas", "BAR=other"])]
     fn parse_csv_env_when_ok(#[case] data: &str, #[case] expected: Vec<&str>) {
-        let result = parse_csv_env(data).unwrap();
+        let result = parse_csv(data, b',').unwrap();
         assert_eq!(result, expected);
     }
+
+    fn package(name: &str, version: &str, source: Option<&str>, benches: &[&str]) -> Package {
+        Package {
+            dependencies: if benches.is_empty() {
+                vec![]
+            } else {
+                vec![Dependency {
+                    kind: Some("dev".to_owned()),
+                    name: "gungraun".to_owned(),
+                    optional: false,
+                    rename: None,
+                    req: "*".to_owned(),
+                }]
+            },
+            id: format!("{name} {version}"),
+            manifest_path: PathBuf::from(format!("/{name}/Cargo.toml")),
+            name: name.to_owned(),
+            source: source.map(ToOwned::to_owned),
+            targets: benches
+                .iter()
+                .map(|bench| PackageTarget {
+                    harness: false,
+                    kind: vec!["bench".to_owned()],
+                    name: (*bench).to_owned(),
+                    required_features: vec![],
+                    src_path: PathBuf::from(format!("/{name}/benches/{bench}.rs")),
+                })
+                .collect(),
+            version: version.to_owned(),
+            ..Package::default()
+        }
+    }
+
+    /// The workspace members `(name, gungraun version, benches)` and their gungraun packages
+    fn metadata(members: &[(&str, &str, &[&str])]) -> CargoMetadata {
+        let registry = "registry+https://github.com/rust-lang/crates.io-index";
+        let mut packages = vec![];
+        let mut nodes = vec![];
+        for (name, version, benches) in members {
+            let member = package(name, "0.1.0", None, benches);
+            let gungraun = package("gungraun", version, Some(registry), &[]);
+            nodes.push(Node {
+                deps: vec![NodeDep {
+                    name: "gungraun".to_owned(),
+                    pkg: gungraun.id.clone(),
+                }],
+                id: member.id.clone(),
+            });
+            packages.push(member);
+            if !packages.contains(&gungraun) {
+                packages.push(gungraun);
+            }
+        }
+
+        CargoMetadata {
+            packages,
+            resolve: Some(Resolve { nodes }),
+            target_directory: PathBuf::from("/target"),
+            workspace_members: members
+                .iter()
+                .map(|(name, ..)| format!("{name} 0.1.0"))
+                .collect(),
+            workspace_root: PathBuf::from("/"),
+            ..CargoMetadata::default()
+        }
+    }
+
+    /// The `cargo metadata` output of a workspace with a gungraun, a criterion and a libtest
+    /// bench
+    fn workspace_metadata(config: &str) -> CargoMetadata {
+        let json = format!(
+            r#"{{
+                "metadata": {{ "gungraun": {config} }},
+                "packages": [
+                    {{
+                        "dependencies": [
+                            {{ "kind": "dev", "name": "gungraun", "req": "^0.18" }},
+                            {{ "kind": null, "name": "serde", "optional": true, "req": "^1" }}
+                        ],
+                        "features": {{ "default": ["serde"], "serde": ["dep:serde"] }},
+                        "id": "a 0.1.0",
+                        "manifest_path": "/ws/a/Cargo.toml",
+                        "metadata": {{ "docs": {{ "rs": {{ "all-features": true }} }} }},
+                        "name": "a",
+                        "source": null,
+                        "targets": [
+                            {{ "kind": ["lib"], "name": "a", "src_path": "/ws/a/src/lib.rs" }},
+                            {{
+                                "harness": false,
+                                "kind": ["bench"],
+                                "name": "gungraun-bench",
+                                "required-features": ["serde"],
+                                "src_path": "/ws/a/benches/gungraun.rs"
+                            }},
+                            {{ "kind": ["bench"], "name": "libtest", "src_path": "/ws/a/benches/l.rs" }}
+                        ],
+                        "version": "0.1.0"
+                    }},
+                    {{
+                        "dependencies": [{{ "kind": "dev", "name": "criterion", "req": "^0.5" }}],
+                        "id": "b 0.1.0",
+                        "manifest_path": "/ws/b/Cargo.toml",
+                        "name": "b",
+                        "source": null,
+                        "targets": [
+                            {{
+                                "harness": false,
+                                "kind": ["bench"],
+                                "name": "criterion",
+                                "src_path": "/ws/b/benches/criterion.rs"
+                            }}
+                        ],
+                        "version": "0.1.0"
+                    }}
+                ],
+                "target_directory": "/ws/target",
+                "workspace_members": ["a 0.1.0", "b 0.1.0"],
+                "workspace_root": "/ws"
+            }}"#
+        );
+        serde_json::from_str(&json).unwrap()
+    }
+
+    #[test]
+    fn cargo_metadata_deserialize() {
+        let metadata = workspace_metadata("{}");
+        let a = &metadata.packages[0];
+
+        assert_eq!(a.features["serde"], ["dep:serde"]);
+        assert!(a.dependencies[1].optional);
+        assert!(a.metadata.as_ref().unwrap().get("docs").is_some());
+        assert!(a.targets[0].harness);
+        assert_eq!(a.targets[1].required_features, ["serde"]);
+        assert!(metadata.packages[1].metadata.is_none());
+        assert!(metadata.resolve.is_none());
+    }
+
+    #[test]
+    fn cargo_metadata_benches() {
+        let metadata = workspace_metadata("{}");
+
+        assert_eq!(
+            metadata
+                .benches()
+                .iter()
+                .map(|b| (b.package.name.as_str(), b.target.name.as_str(), b.harness))
+                .collect::<Vec<_>>(),
+            [
+                ("a", "gungraun-bench", Harness::Gungraun),
+                ("a", "libtest", Harness::Libtest),
+                ("b", "criterion", Harness::Criterion)
+            ]
+        );
+    }
+
+    #[rstest]
+    #[case::empty(&[], &[])]
+    #[case::package(&["a"], &[])]
+    #[case::bench(&[], &["criterion"])]
+    #[case::package_and_bench(&["a"], &["libtest"])]
+    #[case::patterns(&["c*"], &["x?"])]
+    fn check_selection_when_valid(#[case] packages: &[&str], #[case] benches: &[&str]) {
+        let selection = Selection {
+            benches: benches.iter().map(ToString::to_string).collect(),
+            packages: packages.iter().map(ToString::to_string).collect(),
+        };
+        workspace_metadata("{}")
+            .check_selection(&selection)
+            .unwrap();
+    }
+
+    #[rstest]
+    #[case::no_member(
+        &["c"],
+        &[],
+        "The package 'c' is not a member of the workspace. Available packages: a, b"
+    )]
+    #[case::no_bench(
+        &[],
+        &["missing"],
+        "No bench target named 'missing'. Available benches: gungraun-bench, libtest, criterion"
+    )]
+    #[case::bench_in_other_package(
+        &["b"],
+        &["libtest"],
+        "No bench target named 'libtest'. Available benches: criterion"
+    )]
+    fn check_selection_when_invalid(
+        #[case] packages: &[&str],
+        #[case] benches: &[&str],
+        #[case] expected: &str,
+    ) {
+        let selection = Selection {
+            benches: benches.iter().map(ToString::to_string).collect(),
+            packages: packages.iter().map(ToString::to_string).collect(),
+        };
+        assert_eq!(
+            workspace_metadata("{}")
+                .check_selection(&selection)
+                .unwrap_err()
+                .to_string(),
+            expected
+        );
+    }
+
+    #[rstest]
+    #[case::all(&[], &[], &["gungraun-bench", "libtest", "criterion"])]
+    #[case::package(&["b"], &[], &["criterion"])]
+    #[case::bench(&[], &["libtest"], &["libtest"])]
+    #[case::pattern(&["a"], &["*bench"], &["gungraun-bench"])]
+    fn cargo_metadata_selected_benches(
+        #[case] packages: &[&str],
+        #[case] benches: &[&str],
+        #[case] expected: &[&str],
+    ) {
+        let selection = Selection {
+            benches: benches.iter().map(ToString::to_string).collect(),
+            packages: packages.iter().map(ToString::to_string).collect(),
+        };
+        assert_eq!(
+            workspace_metadata("{}")
+                .selected_benches(&selection)
+                .iter()
+                .map(|b| b.target.name.as_str())
+                .collect::<Vec<_>>(),
+            expected
+        );
+    }
+
+    #[rstest]
+    #[case::error("error", HarnessPolicy::Error)]
+    #[case::run_qemu_user("run-qemu-user", HarnessPolicy::RunQemuUser)]
+    #[case::run_vm("run-vm", HarnessPolicy::RunVm)]
+    #[case::skip("skip", HarnessPolicy::Skip)]
+    fn harness_policy_parse(#[case] value: &str, #[case] expected: HarnessPolicy) {
+        assert_eq!(value.parse::<HarnessPolicy>().unwrap(), expected);
+        assert_eq!(expected.to_string(), value);
+    }
+
+    #[test]
+    fn harness_policy_parse_when_invalid() {
+        assert_eq!(
+            "vm".parse::<HarnessPolicy>().unwrap_err().to_string(),
+            "Invalid policy 'vm'. Possible values are skip, run-qemu-user, run-vm, error"
+        );
+    }
+
+    #[test]
+    fn cargo_metadata_config() {
+        assert_eq!(
+            workspace_metadata(r#"{ "env-file": "bench.env", "non-gungraun": "run-qemu-user" }"#)
+                .config()
+                .unwrap(),
+            Config {
+                env_file: Some(PathBuf::from("bench.env")),
+                non_gungraun: Some(HarnessPolicy::RunQemuUser),
+            }
+        );
+        assert_eq!(
+            workspace_metadata(r#"{ "unknown": 1 }"#)
+                .config()
+                .unwrap_err()
+                .to_string(),
+            "Invalid [workspace.metadata.gungraun] table"
+        );
+    }
+
+    #[test]
+    fn gungraun_uses_when_single_version() {
+        let metadata = metadata(&[("a", "0.18.0", &["bench-a"]), ("b", "0.18.0", &["bench_b"])]);
+
+        let uses = metadata.gungraun_uses().unwrap();
+        assert_eq!(uses.len(), 1);
+        assert_eq!(uses[0].benches, ["bench_a", "bench_b"]);
+        assert_eq!(uses[0].gungraun.version, "0.18.0");
+        assert!(extra_runners(&uses, false).unwrap().is_empty());
+    }
+
+    #[test]
+    fn gungraun_uses_when_multiple_versions() {
+        let metadata = metadata(&[
+            ("a", "0.17.1", &["old"]),
+            ("b", "0.18.0", &["new", "newer"]),
+        ]);
+
+        let uses = metadata.gungraun_uses().unwrap();
+        assert_eq!(metadata.gungraun_version().as_deref(), Some("0.18.0"));
+        assert_eq!(
+            extra_runners(&uses, false).unwrap(),
+            [ExtraRunner {
+                benches: vec!["old".to_owned()],
+                version: "0.17.1".to_owned()
+            }]
+        );
+        assert_eq!(
+            extra_runners(&uses, true).unwrap_err().to_string(),
+            "GUNGRAUN_RUNNER can only be used with a single gungraun version but the benches use \
+             gungraun 0.18.0, gungraun 0.17.1"
+        );
+    }
+
+    #[test]
+    fn gungraun_uses_when_same_bench_name() {
+        let metadata = metadata(&[("a", "0.17.1", &["bench"]), ("b", "0.18.0", &["bench"])]);
+
+        assert_eq!(
+            metadata.gungraun_uses().unwrap_err().to_string(),
+            "The benches named 'bench' use gungraun 0.17.1 and gungraun 0.18.0. Rename one of them \
+             or use the same gungraun version in all packages"
+        );
+    }
+
+    #[rstest]
+    #[case::default(
+        "stable-x86_64-unknown-linux-gnu (default)\n",
+        Some("stable-x86_64-unknown-linux-gnu")
+    )]
+    #[case::toolchain_file(
+        "1.80-x86_64-unknown-linux-gnu (overridden by '/ws/rust-toolchain.toml')\n",
+        Some("1.80-x86_64-unknown-linux-gnu")
+    )]
+    #[case::name_only(
+        "nightly-x86_64-unknown-linux-gnu",
+        Some("nightly-x86_64-unknown-linux-gnu")
+    )]
+    #[case::empty("", None)]
+    fn parse_active_toolchain_when(#[case] output: &str, #[case] expected: Option<&str>) {
+        assert_eq!(parse_active_toolchain(output), expected);
+    }
+
+    #[rstest]
+    #[case::docker_rootless(Engine::Docker, true, None, true)]
+    #[case::docker_rootful(Engine::Docker, false, None, false)]
+    #[case::podman_rootful(Engine::Podman, false, None, false)]
+    #[case::podman_keep_id(Engine::Podman, true, Some("keep-id"), false)]
+    #[case::podman_auto(Engine::PodmanRemote, true, Some("auto"), false)]
+    #[case::podman_empty(Engine::Podman, true, Some(""), true)]
+    #[case::podman_host(Engine::Podman, true, Some("host"), true)]
+    #[case::podman_none(Engine::Podman, true, None, true)]
+    fn is_root_mapped_when(
+        #[case] engine: Engine,
+        #[case] rootless: bool,
+        #[case] userns: Option<&str>,
+        #[case] expected: bool,
+    ) {
+        assert_eq!(is_root_mapped(engine, rootless, userns), expected);
+    }
+
+    #[test]
+    fn host_data_when_manifest_path_does_not_exist() {
+        let error = HostData::new(
+            true,
+            Some(Utf8Path::new("/does/not/exist/Cargo.toml")),
+            None,
+        )
+        .err()
+        .unwrap();
+
+        assert_eq!(Error::exit_code_of(&error), 2);
+        assert!(error
+            .to_string()
+            .starts_with("Invalid --manifest-path '/does/not/exist/Cargo.toml': "));
+    }
 }